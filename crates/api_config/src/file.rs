@@ -0,0 +1,194 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::TlsConfig;
+
+// every value is optional, environment variables override the file and
+// built-in defaults cover the rest. a SIGHUP reloads watchlists, aliases, the
+// [upstream] section and the provider limits. server, cache, auth and the
+// provider base urls are structural and need a restart
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub server: ServerSection,
+    pub cache: CacheSection,
+    // shared by the providers
+    pub upstream: UpstreamSection,
+    pub providers: ProvidersSection,
+    pub auth: AuthSection,
+    // name to series like `moex:sber`, usable in `series=` lists
+    pub watchlists: BTreeMap<String, Vec<String>>,
+    pub aliases: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSection {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub workers: Option<usize>,
+    pub tls: Option<TlsConfig>,
+    pub unix_socket: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheSection {
+    pub redis_url: Option<String>,
+    // splits and dividends
+    pub actions_ttl_seconds: Option<u64>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpstreamSection {
+    pub connect_timeout_ms: Option<u64>,
+    pub timeout_ms: Option<u64>,
+    pub read_timeout_ms: Option<u64>,
+    pub retries: Option<u32>,
+    pub backoff_ms: Option<u64>,
+    pub breaker_threshold: Option<u32>,
+    pub breaker_cooldown_ms: Option<u64>,
+    pub max_in_flight: Option<usize>,
+    pub requests_per_second: Option<f64>,
+}
+
+// everything is off by default
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthSection {
+    // no keys means no authentication
+    pub api_keys: Option<Vec<String>>,
+    // requests per minute, 0 is unlimited
+    pub key_rate_limit: Option<u32>,
+    pub ip_rate_limit: Option<u32>,
+    pub trust_forwarded: Option<bool>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProvidersSection {
+    pub moex: ProviderSection,
+    pub spbex: ProviderSection,
+    pub cbr: ProviderSection,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProviderSection {
+    pub base_url: Option<String>,
+    pub max_in_flight: Option<usize>,
+    pub requests_per_second: Option<f64>,
+}
+
+impl ConfigFile {
+    // `EXCHANGE_API_CONFIG` points at the file, without it everything comes from the environment
+    pub fn load() -> Result<ConfigFile, Box<dyn Error>> {
+        match ConfigFile::path() {
+            Some(path) => {
+                let content =
+                    fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                ConfigFile::parse(&content).map_err(|e| format!("{}: {}", path.display(), e).into())
+            }
+            None => Ok(ConfigFile::default()),
+        }
+    }
+
+    pub fn parse(content: &str) -> Result<ConfigFile, Box<dyn Error>> {
+        Ok(toml::from_str(content)?)
+    }
+
+    // settings read once at startup, changing them needs a restart
+    pub fn same_structure(&self, other: &ConfigFile) -> bool {
+        let base_urls = |providers: &ProvidersSection| {
+            [&providers.moex, &providers.spbex, &providers.cbr].map(|p| p.base_url.clone())
+        };
+        self.server == other.server
+            && self.cache == other.cache
+            && self.auth == other.auth
+            && base_urls(&self.providers) == base_urls(&other.providers)
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        crate::env_path("EXCHANGE_API_CONFIG")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = r#"
+        [server]
+        port = 8443
+        workers = 4
+
+        [server.tls]
+        cert = "/etc/exchange_api/cert.pem"
+        key = "/etc/exchange_api/key.pem"
+
+        [cache]
+        redis_url = "redis://redis-cache:6379"
+
+        [upstream]
+        read_timeout_ms = 10000
+
+        [providers.spbex]
+        base_url = "http://localhost:9000/api"
+        requests_per_second = 2.0
+
+        [auth]
+        api_keys = ["secret"]
+        ip_rate_limit = 120
+
+        [watchlists]
+        bluechips = ["moex:sber", "moex:gazp"]
+
+        [aliases]
+        usd = "cbr:usd"
+    "#;
+
+    #[test]
+    fn parse_pass() {
+        let file = ConfigFile::parse(EXAMPLE).unwrap();
+        assert_eq!(file.server.port, Some(8443));
+        assert_eq!(file.server.host, None);
+        assert_eq!(
+            file.server.tls.unwrap().cert,
+            PathBuf::from("/etc/exchange_api/cert.pem")
+        );
+        assert_eq!(file.upstream.read_timeout_ms, Some(10000));
+        assert_eq!(file.providers.spbex.requests_per_second, Some(2.0));
+        assert_eq!(file.providers.moex, ProviderSection::default());
+        assert_eq!(file.auth.api_keys, Some(vec!["secret".to_string()]));
+        assert_eq!(file.auth.ip_rate_limit, Some(120));
+        assert_eq!(file.watchlists["bluechips"], vec!["moex:sber", "moex:gazp"]);
+        assert_eq!(file.aliases["usd"], "cbr:usd");
+    }
+
+    #[test]
+    fn parse_fail_unknown_key() {
+        assert!(ConfigFile::parse("[server]\nprot = 8080").is_err());
+    }
+
+    #[test]
+    fn same_structure_pass_ignores_reloadable() {
+        let file = ConfigFile::parse(EXAMPLE).unwrap();
+        let mut changed = file.clone();
+        changed
+            .aliases
+            .insert("eur".to_string(), "cbr:eur".to_string());
+        changed.watchlists.clear();
+        changed.upstream.retries = Some(5);
+        changed.providers.spbex.requests_per_second = Some(0.5);
+        assert!(file.same_structure(&changed));
+        changed.providers.spbex.base_url = Some("http://localhost:8081".to_string());
+        assert!(!file.same_structure(&changed));
+        changed.providers.spbex.base_url = file.providers.spbex.base_url.clone();
+        changed.server.port = Some(8080);
+        assert!(!file.same_structure(&changed));
+    }
+}
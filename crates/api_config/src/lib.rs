@@ -0,0 +1,177 @@
+use serde::Deserialize;
+use std::env;
+use std::error::Error;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub use file::{
+    AuthSection, CacheSection, ConfigFile, ProviderSection, ProvidersSection, ServerSection,
+    UpstreamSection,
+};
+
+mod file;
+
+// unset or empty variables keep the default
+pub fn env_or<T>(name: &str, default: T) -> Result<T, Box<dyn Error>>
+where
+    T: FromStr,
+    T::Err: Error + 'static,
+{
+    match env::var(name) {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse()
+            .map_err(|e| format!("{}: {}", name, e).into()),
+        _ => Ok(default),
+    }
+}
+
+// for settings without a default, the file value is used when the variable is unset
+pub fn env_opt<T>(name: &str, file: Option<T>) -> Result<Option<T>, Box<dyn Error>>
+where
+    T: FromStr,
+    T::Err: Error + 'static,
+{
+    match env::var(name) {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e| format!("{}: {}", name, e).into()),
+        _ => Ok(file),
+    }
+}
+
+fn env_path(name: &str) -> Option<PathBuf> {
+    env::var(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| PathBuf::from(value.trim()))
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    // pem, the leaf certificate first
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+// where exchange_api listens, the healthcheck binary reads it to find the server
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    pub tls: Option<TlsConfig>,
+    // served instead of tcp when set
+    pub unix_socket: Option<PathBuf>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+            tls: None,
+            unix_socket: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    // the environment takes precedence over the file section
+    pub fn load(file: &ServerSection) -> Result<ServerConfig, Box<dyn Error>> {
+        let defaults = ServerConfig::default();
+        let tls = match (
+            env_path("EXCHANGE_API_TLS_CERT"),
+            env_path("EXCHANGE_API_TLS_KEY"),
+        ) {
+            (Some(cert), Some(key)) => Some(TlsConfig { cert, key }),
+            (None, None) => file.tls.clone(),
+            _ => {
+                return Err("EXCHANGE_API_TLS_CERT and EXCHANGE_API_TLS_KEY go together".into());
+            }
+        };
+
+        let config = ServerConfig {
+            host: env_or(
+                "EXCHANGE_API_HOST",
+                file.host.clone().unwrap_or(defaults.host),
+            )?,
+            port: env_or("EXCHANGE_API_PORT", file.port.unwrap_or(defaults.port))?,
+            tls,
+            unix_socket: env_path("EXCHANGE_API_UNIX_SOCKET").or_else(|| file.unix_socket.clone()),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.unix_socket.is_some() && self.tls.is_some() {
+            return Err("TLS is not supported on a unix socket".into());
+        }
+        Ok(())
+    }
+
+    // a wildcard bind address is reached through localhost
+    pub fn local_url(&self, path: &str) -> String {
+        if self.unix_socket.is_some() {
+            return format!("http://localhost{}", path);
+        }
+        let scheme = match self.tls {
+            Some(_) => "https",
+            None => "http",
+        };
+        let host = match self.host.as_str() {
+            "0.0.0.0" | "::" | "[::]" => "localhost",
+            host if host.contains(':') && !host.starts_with('[') => {
+                return format!("{}://[{}]:{}{}", scheme, host, self.port, path);
+            }
+            host => host,
+        };
+        format!("{}://{}:{}{}", scheme, host, self.port, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_url_pass_default() {
+        assert_eq!(
+            ServerConfig::default().local_url("/healthcheck"),
+            "http://localhost:8080/healthcheck"
+        );
+    }
+
+    #[test]
+    fn local_url_pass_tls_and_ipv6() {
+        let config = ServerConfig {
+            host: "::1".to_string(),
+            port: 8443,
+            tls: Some(TlsConfig {
+                cert: PathBuf::from("cert.pem"),
+                key: PathBuf::from("key.pem"),
+            }),
+            unix_socket: None,
+        };
+        assert_eq!(
+            config.local_url("/healthcheck"),
+            "https://[::1]:8443/healthcheck"
+        );
+    }
+
+    #[test]
+    fn validate_fail_tls_on_unix_socket() {
+        let config = ServerConfig {
+            tls: Some(TlsConfig {
+                cert: PathBuf::from("cert.pem"),
+                key: PathBuf::from("key.pem"),
+            }),
+            unix_socket: Some(PathBuf::from("/run/exchange_api.sock")),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+}
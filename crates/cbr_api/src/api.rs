@@ -1,27 +1,54 @@
 use chrono::NaiveDate;
 use history_model::HistoryEntry;
-use log::debug;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
+use tracing::{debug, instrument};
+use upstream_client::{UpstreamClient, UpstreamConfig, UpstreamStatus};
 
 pub struct CbrAPI {
     base_url: String,
-    client: reqwest::Client,
+    client: UpstreamClient,
     headers: reqwest::header::HeaderMap,
 }
 
+impl Default for CbrAPI {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CbrAPI {
     pub fn new() -> Self {
+        Self::with_config(UpstreamConfig::default())
+    }
+
+    pub fn with_config(upstream: UpstreamConfig) -> Self {
         let mut reqwest_headers = reqwest::header::HeaderMap::new();
         reqwest_headers.insert(reqwest::header::USER_AGENT, "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36".parse().unwrap());
 
-        return CbrAPI {
+        CbrAPI {
             base_url: "https://www.cbr.ru".to_string(),
-            client: reqwest::Client::new(),
+            client: UpstreamClient::with_config("cbr", upstream),
             headers: reqwest_headers,
-        };
+        }
+    }
+
+    pub fn with_base_url(self, base_url: impl Into<String>) -> Self {
+        CbrAPI {
+            base_url: base_url.into(),
+            ..self
+        }
+    }
+
+    pub fn upstream_status(&self) -> UpstreamStatus {
+        self.client.status()
+    }
+
+    pub fn reconfigure(&self, upstream: UpstreamConfig) {
+        self.client.reconfigure(upstream);
     }
 
+    #[instrument(skip(self), fields(exchange = "cbr"))]
     pub async fn get_ticker(&self, ticker: &str) -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
         let code = self.map_ticker_to_code(ticker);
         let start_date = "01/01/2014";
@@ -50,6 +77,7 @@ impl CbrAPI {
             .iter()
             .map(|r| HistoryEntry {
                 date: NaiveDate::parse_from_str(&r.date, "%d.%m.%Y").unwrap_or_default(),
+                open: self.parse_cbr_float(&r.vunit_rate),
                 close: self.parse_cbr_float(&r.vunit_rate),
                 low: self.parse_cbr_float(&r.vunit_rate),
                 high: self.parse_cbr_float(&r.vunit_rate),
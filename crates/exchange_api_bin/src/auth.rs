@@ -0,0 +1,317 @@
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderMap};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpResponse, web};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::HealthcheckResponse;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+// container and load balancer probes have no key, the per-ip limit still applies
+const PUBLIC_PATHS: &[&str] = &["/healthcheck", "/health/live", "/health/ready"];
+// clients tracked per limiter, idle and then least recently seen ones are forgotten
+const MAX_TRACKED: usize = 10_000;
+
+// keys and limits for the public server, everything is off by default
+#[derive(Debug, Default)]
+pub struct Auth {
+    keys: HashSet<String>,
+    per_key: Option<KeyedLimiter>,
+    per_ip: Option<KeyedLimiter>,
+    // take the client address the proxy appended to X-Forwarded-For / Forwarded,
+    // only behind exactly one trusted proxy
+    trust_forwarded: bool,
+}
+
+impl Auth {
+    // limits are requests per minute, 0 is unlimited
+    pub fn new(keys: Vec<String>, key_limit: u32, ip_limit: u32, trust_forwarded: bool) -> Self {
+        Auth {
+            keys: keys.into_iter().filter(|k| !k.is_empty()).collect(),
+            per_key: (key_limit > 0).then(|| KeyedLimiter::new(key_limit)),
+            per_ip: (ip_limit > 0).then(|| KeyedLimiter::new(ip_limit)),
+            trust_forwarded,
+        }
+    }
+
+    fn client_ip(&self, req: &ServiceRequest) -> String {
+        if self.trust_forwarded
+            && let Some(addr) = forwarded_client(req.headers())
+        {
+            return addr;
+        }
+        req.connection_info()
+            .peer_addr()
+            .unwrap_or("unknown")
+            .to_string()
+    }
+}
+
+// the right-most entry is the one the proxy added, anything left of it comes from the client
+fn forwarded_client(headers: &HeaderMap) -> Option<String> {
+    let last = |name| {
+        headers
+            .get_all(name)
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .rfind(|entry| !entry.is_empty())
+    };
+    if let Some(addr) = last(header::X_FORWARDED_FOR) {
+        return Some(addr.to_string());
+    }
+    last(header::FORWARDED)?
+        .split(';')
+        .find_map(|pair| pair.trim().strip_prefix("for="))
+        .map(|addr| addr.trim_matches('"').to_string())
+}
+
+fn api_key(req: &ServiceRequest) -> Option<&str> {
+    let headers = req.headers();
+    if let Some(key) = headers.get(API_KEY_HEADER) {
+        return key.to_str().ok();
+    }
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+pub async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let Some(auth) = req.app_data::<web::Data<Auth>>().cloned() else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let now = Instant::now();
+    if let Some(per_ip) = &auth.per_ip
+        && let Err(retry_after) = per_ip.check(&auth.client_ip(&req), now)
+    {
+        return Ok(req
+            .into_response(too_many_requests(retry_after))
+            .map_into_right_body());
+    }
+
+    if !auth.keys.is_empty() && !PUBLIC_PATHS.contains(&req.path()) {
+        let Some(key) = api_key(&req).filter(|key| auth.keys.contains(*key)) else {
+            let response = HttpResponse::Unauthorized()
+                .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+                .json(HealthcheckResponse {
+                    status: "invalid or missing api key".to_string(),
+                });
+            return Ok(req.into_response(response).map_into_right_body());
+        };
+        if let Some(per_key) = &auth.per_key
+            && let Err(retry_after) = per_key.check(key, now)
+        {
+            return Ok(req
+                .into_response(too_many_requests(retry_after))
+                .map_into_right_body());
+        }
+    }
+
+    Ok(next.call(req).await?.map_into_left_body())
+}
+
+fn too_many_requests(retry_after: Duration) -> HttpResponse {
+    // whole seconds, rounded up so a retry right on time is allowed
+    let seconds = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    HttpResponse::TooManyRequests()
+        .insert_header((header::RETRY_AFTER, seconds.to_string()))
+        .json(HealthcheckResponse {
+            status: "rate limit exceeded".to_string(),
+        })
+}
+
+// token bucket per client holding up to a minute of requests
+#[derive(Debug)]
+pub struct KeyedLimiter {
+    per_minute: u32,
+    max_tracked: usize,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl KeyedLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        Self::with_max_tracked(per_minute, MAX_TRACKED)
+    }
+
+    fn with_max_tracked(per_minute: u32, max_tracked: usize) -> Self {
+        KeyedLimiter {
+            per_minute,
+            max_tracked: max_tracked.max(10),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn per_second(&self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.per_second()).min(self.per_minute as f64)
+    }
+
+    // takes a request from the client budget, or tells how long until there is one
+    pub fn check(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= self.max_tracked && !buckets.contains_key(client) {
+            self.evict(&mut buckets, now);
+        }
+
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: self.per_minute as f64,
+            updated: now,
+        });
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64(
+            (1.0 - bucket.tokens) / self.per_second(),
+        ))
+    }
+
+    // full buckets carry no state, then the least recently seen clients go.
+    // a tenth of the map is freed at once so the scan is not repeated for every new client
+    fn evict(&self, buckets: &mut HashMap<String, Bucket>, now: Instant) {
+        let capacity = self.per_minute as f64;
+        buckets.retain(|_, bucket| self.refilled(bucket, now) < capacity);
+
+        let target = self.max_tracked - self.max_tracked / 10;
+        if buckets.len() > target {
+            let excess = buckets.len() - target;
+            let mut updated: Vec<Instant> = buckets.values().map(|bucket| bucket.updated).collect();
+            let (_, cutoff, _) = updated.select_nth_unstable(excess - 1);
+            let cutoff = *cutoff;
+            buckets.retain(|_, bucket| bucket.updated > cutoff);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::App;
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{TestRequest, call_service, init_service};
+
+    #[test]
+    fn limiter_pass_budget_and_refill() {
+        let limiter = KeyedLimiter::new(2);
+        let now = Instant::now();
+        assert!(limiter.check("a", now).is_ok());
+        assert!(limiter.check("a", now).is_ok());
+        assert_eq!(limiter.check("a", now), Err(Duration::from_secs(30)));
+        // other clients have their own budget
+        assert!(limiter.check("b", now).is_ok());
+        assert!(limiter.check("a", now + Duration::from_secs(30)).is_ok());
+    }
+
+    #[test]
+    fn limiter_pass_max_tracked() {
+        let limiter = KeyedLimiter::with_max_tracked(1, 10);
+        let now = Instant::now();
+        for i in 0..25 {
+            let at = now + Duration::from_millis(i);
+            assert!(limiter.check(&i.to_string(), at).is_ok());
+            assert!(limiter.buckets.lock().unwrap().len() <= 10);
+        }
+        // the latest clients keep their spent budget
+        assert!(
+            limiter
+                .check("24", now + Duration::from_millis(30))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn forwarded_client_pass_right_most() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(forwarded_client(&headers), None);
+        headers.insert(
+            header::FORWARDED,
+            "for=1.1.1.1, for=\"10.0.0.1\";proto=https".parse().unwrap(),
+        );
+        assert_eq!(forwarded_client(&headers).as_deref(), Some("10.0.0.1"));
+        headers.insert(
+            header::X_FORWARDED_FOR,
+            "6.6.6.6, 10.0.0.2".parse().unwrap(),
+        );
+        assert_eq!(forwarded_client(&headers).as_deref(), Some("10.0.0.2"));
+    }
+
+    async fn ok() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_web::test]
+    async fn authenticate_pass() {
+        let auth = web::Data::new(Auth::new(vec!["secret".to_string()], 1, 0, false));
+        let app = init_service(
+            App::new()
+                .app_data(auth)
+                .route("/healthcheck", web::get().to(ok))
+                .route("/moex/{ticker}", web::get().to(ok))
+                .wrap(from_fn(authenticate)),
+        )
+        .await;
+
+        let req = TestRequest::get().uri("/healthcheck").to_request();
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = TestRequest::get().uri("/moex/sber").to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let req = TestRequest::get()
+            .uri("/moex/sber")
+            .insert_header((header::AUTHORIZATION, "Bearer secret"))
+            .to_request();
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = TestRequest::get()
+            .uri("/moex/sber")
+            .insert_header((API_KEY_HEADER, "secret"))
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers().get(header::RETRY_AFTER).unwrap(), "60");
+    }
+
+    #[actix_web::test]
+    async fn authenticate_pass_public_paths_limited() {
+        let auth = web::Data::new(Auth::new(vec!["secret".to_string()], 0, 1, false));
+        let app = init_service(
+            App::new()
+                .app_data(auth)
+                .route("/health/ready", web::get().to(ok))
+                .wrap(from_fn(authenticate)),
+        )
+        .await;
+
+        let req = TestRequest::get().uri("/health/ready").to_request();
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+        let req = TestRequest::get().uri("/health/ready").to_request();
+        assert_eq!(
+            call_service(&app, req).await.status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use actix_web::{HttpResponse, post, web};
+use futures::future::join_all;
+use history_model::HistoryEntry;
+use serde::{Deserialize, Serialize};
+
+use crate::HealthcheckResponse;
+use crate::history::{self, HistoryQuery};
+use crate::providers::{Exchange, Providers, SeriesId};
+
+const MAX_BATCH_ITEMS: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct BatchItem {
+    exchange: Exchange,
+    ticker: String,
+    #[serde(flatten)]
+    query: HistoryQuery,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchResult {
+    exchange: Exchange,
+    ticker: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    history: Option<Vec<HistoryEntry>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[post("/batch")]
+async fn batch(items: web::Json<Vec<BatchItem>>, providers: web::Data<Providers>) -> HttpResponse {
+    if items.len() > MAX_BATCH_ITEMS {
+        return HttpResponse::BadRequest().json(HealthcheckResponse {
+            status: format!("too many items, max {}", MAX_BATCH_ITEMS),
+        });
+    }
+
+    let results = join_all(items.iter().map(|item| fetch_item(item, &providers))).await;
+    HttpResponse::Ok().json(results)
+}
+
+async fn fetch_item(item: &BatchItem, providers: &Providers) -> BatchResult {
+    let series = SeriesId::new(item.exchange, &item.ticker);
+    let (history, error) = match history::load(&series, &item.query, providers).await {
+        Ok(history) => (Some(history), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+
+    BatchResult {
+        exchange: series.exchange,
+        ticker: series.ticker,
+        history,
+        error,
+    }
+}
@@ -0,0 +1,161 @@
+use actix_web::{HttpResponse, get, web};
+use api_config::ConfigFile;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::sync::RwLock;
+
+use crate::providers::{ParseSeriesError, SeriesId};
+use crate::utils;
+
+// aliases and watchlists from the config file, replaced on reload
+#[derive(Debug, Default)]
+pub struct Catalog {
+    entries: RwLock<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    aliases: HashMap<String, SeriesId>,
+    watchlists: BTreeMap<String, Vec<SeriesId>>,
+}
+
+impl Entries {
+    fn from_file(file: &ConfigFile) -> Result<Entries, Box<dyn Error>> {
+        let mut entries = Entries::default();
+        for (name, series) in &file.aliases {
+            let series = series
+                .parse()
+                .map_err(|e| format!("alias {}: {}", name, e))?;
+            entries.aliases.insert(catalog_name(name)?, series);
+        }
+        for (name, list) in &file.watchlists {
+            let series = list
+                .iter()
+                .map(|series| series.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("watchlist {}: {}", name, e))?;
+            let name = catalog_name(name)?;
+            if entries.aliases.contains_key(&name) {
+                return Err(format!("{} is both an alias and a watchlist", name).into());
+            }
+            entries.watchlists.insert(name, series);
+        }
+        Ok(entries)
+    }
+}
+
+fn catalog_name(name: &str) -> Result<String, Box<dyn Error>> {
+    let sanitized = utils::sanitize_ticker(name.to_string());
+    if sanitized.is_empty() {
+        return Err(format!("invalid catalog name: {}", name).into());
+    }
+    Ok(sanitized)
+}
+
+impl Catalog {
+    pub fn from_file(file: &ConfigFile) -> Result<Catalog, Box<dyn Error>> {
+        Ok(Catalog {
+            entries: RwLock::new(Entries::from_file(file)?),
+        })
+    }
+
+    // the current entries are kept when the file is invalid
+    pub fn reload(&self, file: &ConfigFile) -> Result<(), Box<dyn Error>> {
+        let entries = Entries::from_file(file)?;
+        *self.entries.write().unwrap() = entries;
+        Ok(())
+    }
+
+    // comma separated series, aliases and watchlists like `moex:sber,usd,bluechips`
+    pub fn parse_list(&self, list: &str) -> Result<Vec<SeriesId>, ParseSeriesError> {
+        let entries = self.entries.read().unwrap();
+        let mut series = Vec::new();
+        for item in list.split(',').filter(|s| !s.trim().is_empty()) {
+            let name = utils::sanitize_ticker(item.to_string());
+            if item.contains(':') {
+                series.push(item.parse()?);
+            } else if let Some(alias) = entries.aliases.get(&name) {
+                series.push(alias.clone());
+            } else if let Some(watchlist) = entries.watchlists.get(&name) {
+                series.extend(watchlist.iter().cloned());
+            } else {
+                series.push(item.parse()?);
+            }
+        }
+        Ok(series)
+    }
+
+    pub fn watchlists(&self) -> BTreeMap<String, Vec<String>> {
+        let entries = self.entries.read().unwrap();
+        entries
+            .watchlists
+            .iter()
+            .map(|(name, series)| {
+                (
+                    name.clone(),
+                    series.iter().map(SeriesId::to_string).collect(),
+                )
+            })
+            .collect()
+    }
+}
+
+#[get("/watchlists")]
+async fn get_watchlists(catalog: web::Data<Catalog>) -> HttpResponse {
+    HttpResponse::Ok().json(catalog.watchlists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::Exchange;
+
+    fn catalog() -> Catalog {
+        let file = ConfigFile::parse(
+            r#"
+            [watchlists]
+            BlueChips = ["moex:sber", "moex:gazp"]
+
+            [aliases]
+            usd = "cbr:usd"
+            "#,
+        )
+        .unwrap();
+        Catalog::from_file(&file).unwrap()
+    }
+
+    #[test]
+    fn parse_list_pass_aliases_and_watchlists() {
+        let result = catalog().parse_list("bluechips, USD,spbex:aapl,").unwrap();
+        assert_eq!(
+            result,
+            vec![
+                SeriesId::new(Exchange::Moex, "sber"),
+                SeriesId::new(Exchange::Moex, "gazp"),
+                SeriesId::new(Exchange::Cbr, "usd"),
+                SeriesId::new(Exchange::Spbex, "aapl"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_list_fail_unknown_name() {
+        assert!(catalog().parse_list("moex:sber,eur").is_err());
+    }
+
+    #[test]
+    fn reload_fail_keeps_entries() {
+        let catalog = catalog();
+        let mut file = ConfigFile::default();
+        file.aliases
+            .insert("eur".to_string(), "ecb:eur".to_string());
+        assert!(catalog.reload(&file).is_err());
+        assert!(catalog.parse_list("usd").is_ok());
+
+        file.aliases
+            .insert("eur".to_string(), "cbr:eur".to_string());
+        catalog.reload(&file).unwrap();
+        assert!(catalog.parse_list("usd").is_err());
+        assert!(catalog.watchlists().is_empty());
+    }
+}
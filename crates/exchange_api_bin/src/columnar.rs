@@ -0,0 +1,171 @@
+use arrow_array::{ArrayRef, Date32Array, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use chrono::NaiveDate;
+use history_model::HistoryEntry;
+use parquet::arrow::ArrowWriter;
+use std::error::Error;
+use std::sync::Arc;
+
+use crate::providers::SeriesId;
+
+fn history_fields() -> Vec<Field> {
+    vec![
+        Field::new("date", DataType::Date32, false),
+        Field::new("open", DataType::Float64, false),
+        Field::new("close", DataType::Float64, false),
+        Field::new("high", DataType::Float64, false),
+        Field::new("low", DataType::Float64, false),
+        Field::new("volume", DataType::Int64, false),
+        Field::new("facevalue", DataType::Int64, false),
+    ]
+}
+
+fn history_columns<'a>(history: impl Iterator<Item = &'a HistoryEntry> + Clone) -> Vec<ArrayRef> {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap_or_default();
+    vec![
+        Arc::new(Date32Array::from_iter_values(
+            history.clone().map(|e| (e.date - epoch).num_days() as i32),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            history.clone().map(|e| e.open),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            history.clone().map(|e| e.close),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            history.clone().map(|e| e.high),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            history.clone().map(|e| e.low),
+        )),
+        Arc::new(Int64Array::from_iter_values(
+            history.clone().map(|e| e.volume),
+        )),
+        Arc::new(Int64Array::from_iter_values(history.map(|e| e.facevalue))),
+    ]
+}
+
+pub fn history_batch(history: &[HistoryEntry]) -> Result<RecordBatch, ArrowError> {
+    let schema = Schema::new(history_fields());
+    RecordBatch::try_new(Arc::new(schema), history_columns(history.iter()))
+}
+
+// long format: one row per (exchange, ticker, date)
+pub fn series_batch(series: &[(SeriesId, Vec<HistoryEntry>)]) -> Result<RecordBatch, ArrowError> {
+    let mut fields = vec![
+        Field::new("exchange", DataType::Utf8, false),
+        Field::new("ticker", DataType::Utf8, false),
+    ];
+    fields.extend(history_fields());
+
+    let rows = || {
+        series
+            .iter()
+            .flat_map(|(id, history)| history.iter().map(move |entry| (id, entry)))
+    };
+
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from(
+            rows()
+                .map(|(id, _)| id.exchange.as_str())
+                .collect::<Vec<_>>(),
+        )),
+        Arc::new(StringArray::from(
+            rows().map(|(id, _)| id.ticker.as_str()).collect::<Vec<_>>(),
+        )),
+    ];
+    columns.extend(history_columns(rows().map(|(_, entry)| entry)));
+
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+}
+
+pub fn to_parquet(batch: &RecordBatch) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut body = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut body, batch.schema(), None)?;
+    writer.write(batch)?;
+    writer.close()?;
+    Ok(body)
+}
+
+pub fn to_arrow_ipc(batch: &RecordBatch) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut body = Vec::new();
+    let mut writer = FileWriter::try_new(&mut body, &batch.schema())?;
+    writer.write(batch)?;
+    writer.finish()?;
+    drop(writer);
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::Exchange;
+    use arrow_array::Array;
+
+    fn history() -> Vec<HistoryEntry> {
+        vec![
+            HistoryEntry {
+                date: NaiveDate::from_ymd_opt(1970, 1, 2).unwrap(),
+                open: 1.5,
+                close: 1.5,
+                high: 2.0,
+                low: 1.0,
+                volume: 10,
+                facevalue: 1,
+            },
+            HistoryEntry {
+                date: NaiveDate::from_ymd_opt(1970, 1, 3).unwrap(),
+                open: 2.5,
+                close: 2.5,
+                high: 3.0,
+                low: 2.0,
+                volume: 20,
+                facevalue: 1,
+            },
+        ]
+    }
+
+    #[test]
+    fn history_batch_pass_dates() {
+        let batch = history_batch(&history()).unwrap();
+        let dates = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Date32Array>()
+            .unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(dates.values().to_vec(), vec![1, 2]);
+    }
+
+    #[test]
+    fn series_batch_pass_long_format() {
+        let series = vec![
+            (SeriesId::new(Exchange::Moex, "sber"), history()),
+            (SeriesId::new(Exchange::Cbr, "usd"), history()[..1].to_vec()),
+        ];
+        let batch = series_batch(&series).unwrap();
+        let tickers = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(batch.num_columns(), 9);
+        assert_eq!(tickers.value(2), "usd");
+        assert_eq!(tickers.null_count(), 0);
+    }
+
+    #[test]
+    fn to_parquet_pass_magic() {
+        let body = to_parquet(&history_batch(&history()).unwrap()).unwrap();
+        assert_eq!(&body[..4], b"PAR1");
+        assert_eq!(&body[body.len() - 4..], b"PAR1");
+    }
+
+    #[test]
+    fn to_arrow_ipc_pass_magic() {
+        let body = to_arrow_ipc(&history_batch(&history()).unwrap()).unwrap();
+        assert_eq!(&body[..6], b"ARROW1");
+    }
+}
@@ -0,0 +1,51 @@
+use actix_web::{HttpResponse, get, web};
+use history_analytics::align::{self, AlignedRow, Fill};
+use serde::{Deserialize, Serialize};
+
+use crate::HealthcheckResponse;
+use crate::catalog::Catalog;
+use crate::history::{self, HistoryQuery};
+use crate::providers::{Providers, SeriesId};
+
+#[derive(Debug, Deserialize)]
+pub struct CompareQuery {
+    series: String,
+    fill: Option<Fill>,
+}
+
+// same columns/data layout ISS uses for its tables
+#[derive(Debug, Serialize)]
+pub struct CompareResponse {
+    columns: Vec<String>,
+    data: Vec<AlignedRow>,
+}
+
+#[get("/compare")]
+async fn compare(
+    query: web::Query<CompareQuery>,
+    history_query: web::Query<HistoryQuery>,
+    providers: web::Data<Providers>,
+    catalog: web::Data<Catalog>,
+) -> HttpResponse {
+    let series = match history::parse_series_list(&query.series, &catalog) {
+        Ok(series) => series,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(HealthcheckResponse {
+                status: e.to_string(),
+            });
+        }
+    };
+
+    let histories = match history::load_all(&series, &history_query, &providers).await {
+        Ok(histories) => histories,
+        Err(e) => return history::load_failed(e),
+    };
+
+    let mut columns = vec!["date".to_string()];
+    columns.extend(series.iter().map(SeriesId::to_string));
+
+    HttpResponse::Ok().json(CompareResponse {
+        columns,
+        data: align::align(&histories, query.fill.unwrap_or_default()),
+    })
+}
@@ -0,0 +1,89 @@
+use actix_web::{HttpResponse, get, web};
+use history_analytics::correlation::{self, Correlation};
+use serde::{Deserialize, Serialize};
+
+use crate::HealthcheckResponse;
+use crate::catalog::Catalog;
+use crate::history::{self, HistoryQuery};
+use crate::providers::{Exchange, Providers, SeriesId};
+
+const MIN_WINDOW: usize = 2;
+
+#[derive(Debug, Deserialize)]
+pub struct CorrelationQuery {
+    series: String,
+    // latest returns to use, the whole common history otherwise
+    window: Option<usize>,
+    benchmark: Option<String>,
+}
+
+// matrix rows and columns and betas follow `series`
+#[derive(Debug, Serialize)]
+pub struct CorrelationResponse {
+    series: Vec<String>,
+    benchmark: String,
+    #[serde(flatten)]
+    correlation: Correlation,
+}
+
+#[get("/analytics/correlation")]
+async fn get_correlation(
+    query: web::Query<CorrelationQuery>,
+    history_query: web::Query<HistoryQuery>,
+    providers: web::Data<Providers>,
+    catalog: web::Data<Catalog>,
+) -> HttpResponse {
+    if query.window.is_some_and(|window| window < MIN_WINDOW) {
+        return HttpResponse::BadRequest().json(HealthcheckResponse {
+            status: format!("window must be at least {}", MIN_WINDOW),
+        });
+    }
+
+    let series = match history::parse_series_list(&query.series, &catalog) {
+        Ok(series) => series,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(HealthcheckResponse {
+                status: e.to_string(),
+            });
+        }
+    };
+    let benchmark = match &query.benchmark {
+        Some(benchmark) => match benchmark.parse() {
+            Ok(benchmark) => benchmark,
+            Err(e) => {
+                return HttpResponse::BadRequest().json(HealthcheckResponse {
+                    status: format!("benchmark: {}", e),
+                });
+            }
+        },
+        None => SeriesId::new(Exchange::Moex, "imoex"),
+    };
+
+    // the benchmark is loaded once, as an extra column when it is not requested
+    let mut columns = series.clone();
+    let benchmark_column = match columns.iter().position(|id| *id == benchmark) {
+        Some(column) => column,
+        None => {
+            columns.push(benchmark.clone());
+            columns.len() - 1
+        }
+    };
+
+    let histories = match history::load_all(&columns, &history_query, &providers).await {
+        Ok(histories) => histories,
+        Err(e) => return history::load_failed(e),
+    };
+
+    let mut correlation = correlation::correlation(&histories, benchmark_column, query.window);
+    correlation.matrix.truncate(series.len());
+    for row in correlation.matrix.iter_mut() {
+        row.truncate(series.len());
+    }
+    correlation.beta.truncate(series.len());
+
+    HttpResponse::Ok().json(CorrelationResponse {
+        series: series.iter().map(SeriesId::to_string).collect(),
+        benchmark: benchmark.to_string(),
+        correlation,
+    })
+}
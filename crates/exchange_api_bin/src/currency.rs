@@ -0,0 +1,122 @@
+use history_analytics::convert::{self, RateTable};
+use history_model::{Dividend, HistoryEntry};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::error::Error;
+use tracing::debug;
+
+use crate::providers::{Exchange, Providers};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Currency {
+    Rub,
+    Usd,
+    Eur,
+    Cny,
+}
+
+impl Currency {
+    // trading currency the provider quotes in. every moex instrument is taken as
+    // rouble denominated, bonds with a foreign facevalue are converted as if it were roubles
+    pub fn of_exchange(exchange: Exchange) -> Currency {
+        match exchange {
+            Exchange::Moex => Currency::Rub,
+            Exchange::Spbex => Currency::Usd,
+            // cbr rates are rubles per unit of the requested currency
+            Exchange::Cbr => Currency::Rub,
+        }
+    }
+
+    // iso codes as iss reports them, SUR is the old rouble code
+    fn from_iso(code: &str) -> Option<Currency> {
+        match code.to_ascii_uppercase().as_str() {
+            "RUB" | "SUR" => Some(Currency::Rub),
+            "USD" => Some(Currency::Usd),
+            "EUR" => Some(Currency::Eur),
+            "CNY" => Some(Currency::Cny),
+            _ => None,
+        }
+    }
+
+    fn cbr_ticker(&self) -> Option<&'static str> {
+        match self {
+            Currency::Rub => None,
+            Currency::Usd => Some("usd"),
+            Currency::Eur => Some("eur"),
+            Currency::Cny => Some("cny"),
+        }
+    }
+}
+
+// converts date by date using cbr rouble rates, days before the cbr history starts are dropped
+pub async fn convert(
+    history: Vec<HistoryEntry>,
+    source: Currency,
+    target: Currency,
+    providers: &Providers,
+) -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
+    if source == target {
+        return Ok(history);
+    }
+
+    let source_rates = rate_table(source, providers).await?;
+    let target_rates = rate_table(target, providers).await?;
+    let total = history.len();
+    let converted = convert::convert(history, source_rates.as_ref(), target_rates.as_ref());
+    if converted.len() < total {
+        debug!(
+            "dropped {} entries older than the {:?}/{:?} rates",
+            total - converted.len(),
+            source,
+            target
+        );
+    }
+    Ok(converted)
+}
+
+// moex prices are in roubles, dividends paid in another currency are converted
+// at the cbr rate of their registry close date
+pub async fn dividends_in_roubles(
+    dividends: Vec<Dividend>,
+    providers: &Providers,
+) -> Result<Vec<Dividend>, Box<dyn Error>> {
+    let mut tables: HashMap<Currency, Option<RateTable>> = HashMap::new();
+    let mut out = Vec::with_capacity(dividends.len());
+    for dividend in dividends {
+        let currency = Currency::from_iso(&dividend.currency)
+            .ok_or_else(|| format!("dividends in {} are not supported", dividend.currency))?;
+        if currency == Currency::Rub {
+            out.push(dividend);
+            continue;
+        }
+        let table = match tables.entry(currency) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(rate_table(currency, providers).await?),
+        };
+        let rate = table
+            .as_ref()
+            .and_then(|table| table.rate_on(dividend.date))
+            .ok_or_else(|| format!("no {:?} rate on {}", currency, dividend.date))?;
+        out.push(Dividend {
+            value: dividend.value * rate,
+            currency: "RUB".to_string(),
+            ..dividend
+        });
+    }
+    Ok(out)
+}
+
+async fn rate_table(
+    currency: Currency,
+    providers: &Providers,
+) -> Result<Option<RateTable>, Box<dyn Error>> {
+    match currency.cbr_ticker() {
+        Some(ticker) => {
+            let rates = providers.cbr.get_ticker(ticker).await?;
+            Ok(Some(RateTable::from_history(&rates)))
+        }
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,395 @@
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, http::header, web::Bytes};
+use futures::{Stream, StreamExt, stream};
+use history_model::HistoryEntry;
+use serde::Deserialize;
+use std::error::Error;
+use tracing::error;
+
+use crate::HealthcheckResponse;
+use crate::columnar;
+use crate::providers::SeriesId;
+
+const CSV_HEADER: [&str; 7] = [
+    "date",
+    "open",
+    "close",
+    "high",
+    "low",
+    "volume",
+    "facevalue",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Json,
+    Csv,
+    Ndjson,
+    Msgpack,
+    Cbor,
+    Parquet,
+    Arrow,
+}
+
+impl Format {
+    fn from_mime(mime: &str) -> Option<Format> {
+        match mime {
+            "application/json" => Some(Format::Json),
+            "text/csv" => Some(Format::Csv),
+            "application/x-ndjson" => Some(Format::Ndjson),
+            "application/msgpack" | "application/x-msgpack" => Some(Format::Msgpack),
+            "application/cbor" => Some(Format::Cbor),
+            "application/vnd.apache.parquet" => Some(Format::Parquet),
+            "application/vnd.apache.arrow.file" => Some(Format::Arrow),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FormatQuery {
+    pub format: Option<Format>,
+    pub delimiter: Option<char>,
+    pub decimal: Option<char>,
+}
+
+// explicit ?format= wins over the Accept header, json is the fallback
+pub fn negotiate(req: &HttpRequest, query: &FormatQuery) -> Format {
+    if let Some(format) = query.format {
+        return format;
+    }
+
+    req.get_header::<header::Accept>()
+        .and_then(|accept| {
+            accept
+                .ranked()
+                .iter()
+                .find_map(|mime| Format::from_mime(mime.essence_str()))
+        })
+        .unwrap_or(Format::Json)
+}
+
+pub fn respond(req: &HttpRequest, query: &FormatQuery, history: Vec<HistoryEntry>) -> HttpResponse {
+    let format = negotiate(req, query);
+    match format {
+        Format::Json => HttpResponse::Ok().json(history),
+        Format::Csv => {
+            let delimiter = query.delimiter.unwrap_or(',');
+            let decimal = query.decimal.unwrap_or('.');
+            if !delimiter.is_ascii() {
+                return HttpResponse::BadRequest().json(HealthcheckResponse {
+                    status: "invalid delimiter".to_string(),
+                });
+            }
+
+            match to_csv(&history, delimiter as u8, decimal) {
+                Ok(body) => HttpResponse::Ok()
+                    .content_type("text/csv; charset=utf-8")
+                    .body(body),
+                Err(_) => HttpResponse::InternalServerError().finish(),
+            }
+        }
+        Format::Ndjson => {
+            let mut body = Vec::new();
+            for entry in &history {
+                match ndjson_line(entry) {
+                    Ok(line) => body.extend_from_slice(&line),
+                    Err(_) => return HttpResponse::InternalServerError().finish(),
+                }
+            }
+            HttpResponse::Ok()
+                .content_type("application/x-ndjson")
+                .body(body)
+        }
+        Format::Msgpack => match rmp_serde::to_vec_named(&history) {
+            Ok(body) => HttpResponse::Ok()
+                .content_type("application/msgpack")
+                .body(body),
+            Err(_) => HttpResponse::InternalServerError().finish(),
+        },
+        Format::Cbor => {
+            let mut body = Vec::new();
+            match ciborium::into_writer(&history, &mut body) {
+                Ok(()) => HttpResponse::Ok()
+                    .content_type("application/cbor")
+                    .body(body),
+                Err(_) => HttpResponse::InternalServerError().finish(),
+            }
+        }
+        Format::Parquet | Format::Arrow => {
+            let batch = match columnar::history_batch(&history) {
+                Ok(batch) => batch,
+                Err(_) => return HttpResponse::InternalServerError().finish(),
+            };
+            respond_columnar(format, &batch)
+        }
+    }
+}
+
+// multi-ticker export is columnar only, json consumers can call the history endpoints instead
+pub fn respond_export(format: Format, series: &[(SeriesId, Vec<HistoryEntry>)]) -> HttpResponse {
+    if format != Format::Parquet && format != Format::Arrow {
+        return HttpResponse::BadRequest().json(HealthcheckResponse {
+            status: "unsupported format".to_string(),
+        });
+    }
+
+    match columnar::series_batch(series) {
+        Ok(batch) => respond_columnar(format, &batch),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+fn respond_columnar(format: Format, batch: &arrow_array::RecordBatch) -> HttpResponse {
+    let (body, content_type, extension) = match format {
+        Format::Arrow => (
+            columnar::to_arrow_ipc(batch),
+            "application/vnd.apache.arrow.file",
+            "arrow",
+        ),
+        _ => (
+            columnar::to_parquet(batch),
+            "application/vnd.apache.parquet",
+            "parquet",
+        ),
+    };
+
+    match body {
+        Ok(body) => HttpResponse::Ok()
+            .content_type(content_type)
+            .insert_header(header::ContentDisposition::attachment(format!(
+                "history.{}",
+                extension
+            )))
+            .body(body),
+        Err(e) => {
+            error!("respond_columnar | {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// sends every entry as soon as the provider yields it instead of buffering the whole history.
+// the first entry is awaited before the status line, so an unknown ticker or a failing first
+// page gets the same empty result as the other formats. a later upstream error aborts the
+// chunked response so clients don't mistake it for a full history
+pub async fn respond_stream<S>(history: S) -> HttpResponse
+where
+    S: Stream<Item = Result<HistoryEntry, Box<dyn Error>>> + 'static,
+{
+    let mut history = Box::pin(history);
+    let first = match history.next().await {
+        Some(Ok(first)) => first,
+        end => {
+            if let Some(Err(e)) = end {
+                error!("respond_stream | {}", e);
+            }
+            return HttpResponse::Ok()
+                .content_type("application/x-ndjson")
+                .finish();
+        }
+    };
+
+    let body = stream::once(async { Ok(first) })
+        .chain(history)
+        .map(|entry| {
+            let line = entry.and_then(|entry| ndjson_line(&entry).map_err(Into::into));
+            if let Err(e) = &line {
+                error!("respond_stream | {}", e);
+            }
+            line.map(Bytes::from)
+        });
+
+    HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(body)
+}
+
+fn ndjson_line(entry: &HistoryEntry) -> Result<Vec<u8>, serde_json::Error> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    Ok(line)
+}
+
+pub fn to_csv(
+    history: &[HistoryEntry],
+    delimiter: u8,
+    decimal: char,
+) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(vec![]);
+
+    writer.write_record(CSV_HEADER)?;
+    for entry in history {
+        writer.write_record([
+            entry.date.to_string(),
+            format_float(entry.open, decimal),
+            format_float(entry.close, decimal),
+            format_float(entry.high, decimal),
+            format_float(entry.low, decimal),
+            entry.volume.to_string(),
+            entry.facevalue.to_string(),
+        ])?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| csv::Error::from(e.into_error()))
+}
+
+fn format_float(value: f64, decimal: char) -> String {
+    let formatted = value.to_string();
+    if decimal == '.' {
+        return formatted;
+    }
+    formatted.replace('.', &decimal.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test::TestRequest, web};
+    use chrono::NaiveDate;
+
+    fn history() -> Vec<HistoryEntry> {
+        vec![HistoryEntry {
+            date: NaiveDate::from_ymd_opt(2024, 1, 3).unwrap(),
+            open: 271.9,
+            close: 271.9,
+            high: 274.7,
+            low: 270.5,
+            volume: 100,
+            facevalue: 1,
+        }]
+    }
+
+    #[test]
+    fn negotiate_default_json() {
+        let req = TestRequest::default().to_http_request();
+        assert_eq!(negotiate(&req, &FormatQuery::default()), Format::Json);
+    }
+
+    #[test]
+    fn negotiate_accept_csv() {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "text/csv"))
+            .to_http_request();
+        assert_eq!(negotiate(&req, &FormatQuery::default()), Format::Csv);
+    }
+
+    #[test]
+    fn negotiate_accept_ranked() {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/json;q=0.5, text/csv"))
+            .to_http_request();
+        assert_eq!(negotiate(&req, &FormatQuery::default()), Format::Csv);
+    }
+
+    #[test]
+    fn negotiate_query_overrides_accept() {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "text/csv"))
+            .to_http_request();
+        let query = FormatQuery {
+            format: Some(Format::Json),
+            ..Default::default()
+        };
+        assert_eq!(negotiate(&req, &query), Format::Json);
+    }
+
+    #[test]
+    fn negotiate_accept_ndjson() {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/x-ndjson"))
+            .to_http_request();
+        assert_eq!(negotiate(&req, &FormatQuery::default()), Format::Ndjson);
+    }
+
+    #[test]
+    fn negotiate_accept_binary() {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/msgpack"))
+            .to_http_request();
+        assert_eq!(negotiate(&req, &FormatQuery::default()), Format::Msgpack);
+
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/cbor, application/json;q=0.9"))
+            .to_http_request();
+        assert_eq!(negotiate(&req, &FormatQuery::default()), Format::Cbor);
+    }
+
+    #[test]
+    fn negotiate_query_parquet() {
+        let req = TestRequest::with_uri("/moex/sber?format=parquet").to_http_request();
+        let query = web::Query::<FormatQuery>::from_query(req.query_string()).unwrap();
+        assert_eq!(negotiate(&req, &query), Format::Parquet);
+    }
+
+    async fn respond_body(format: Format) -> Bytes {
+        let req = TestRequest::default().to_http_request();
+        let query = FormatQuery {
+            format: Some(format),
+            ..Default::default()
+        };
+        let res = respond(&req, &query, history());
+        assert_eq!(res.status(), actix_web::http::StatusCode::OK);
+        actix_web::body::to_bytes(res.into_body()).await.unwrap()
+    }
+
+    #[actix_web::test]
+    async fn respond_msgpack_round_trip() {
+        let body = respond_body(Format::Msgpack).await;
+        let result: Vec<HistoryEntry> = rmp_serde::from_slice(&body).unwrap();
+        assert_eq!(result, history());
+    }
+
+    #[actix_web::test]
+    async fn respond_cbor_round_trip() {
+        let body = respond_body(Format::Cbor).await;
+        let result: Vec<HistoryEntry> = ciborium::from_reader(&body[..]).unwrap();
+        assert_eq!(result, history());
+    }
+
+    #[actix_web::test]
+    async fn respond_stream_empty_on_first_error() {
+        let failing = stream::iter(vec![Err::<HistoryEntry, Box<dyn Error>>("unknown".into())]);
+        let res = respond_stream(failing).await;
+        assert_eq!(res.status(), actix_web::http::StatusCode::OK);
+        let body = actix_web::body::to_bytes(res.into_body()).await.unwrap();
+        assert!(body.is_empty());
+
+        let entries = stream::iter(history().into_iter().map(Ok));
+        let body = actix_web::body::to_bytes(respond_stream(entries).await.into_body())
+            .await
+            .unwrap();
+        assert_eq!(body, Bytes::from(ndjson_line(&history()[0]).unwrap()));
+    }
+
+    #[test]
+    fn ndjson_line_terminated() {
+        let result = String::from_utf8(ndjson_line(&history()[0]).unwrap()).unwrap();
+        assert_eq!(
+            result,
+            "{\"date\":\"2024-01-03\",\"open\":271.9,\"close\":271.9,\"high\":274.7,\"low\":270.5,\"volume\":100,\"facevalue\":1}\n"
+        );
+    }
+
+    #[test]
+    fn to_csv_default_separators() {
+        let result = String::from_utf8(to_csv(&history(), b',', '.').unwrap()).unwrap();
+        assert_eq!(
+            result,
+            "date,open,close,high,low,volume,facevalue\n2024-01-03,271.9,271.9,274.7,270.5,100,1\n"
+        );
+    }
+
+    #[test]
+    fn to_csv_russian_locale() {
+        let result = String::from_utf8(to_csv(&history(), b';', ',').unwrap()).unwrap();
+        assert_eq!(
+            result,
+            "date;open;close;high;low;volume;facevalue\n2024-01-03;271,9;271,9;274,7;270,5;100;1\n"
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use actix_web::rt::time::timeout;
+use actix_web::{HttpResponse, Responder, get, web};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use upstream_client::UpstreamStatus;
+
+use crate::HealthcheckResponse;
+use crate::providers::Providers;
+
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+// a provider that failed last is still up if it succeeded this recently,
+// one without any request in this window is unknown
+const UPSTREAM_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessResponse {
+    // ok, degraded while some provider is down, unavailable without redis or any provider.
+    // providers nobody used lately are unknown and do not count as down
+    status: &'static str,
+    components: BTreeMap<&'static str, Component>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Component {
+    status: &'static str,
+    // the ping for redis, the latest request for a provider
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_success_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Component {
+    fn upstream(status: UpstreamStatus, now: Instant) -> Component {
+        let state = if status.circuit_open {
+            "down"
+        } else if status.is_idle(now, UPSTREAM_WINDOW) {
+            "unknown"
+        } else if status.is_up(now, UPSTREAM_WINDOW) {
+            "ok"
+        } else {
+            "down"
+        };
+        Component {
+            status: state,
+            latency_ms: status.last_duration.map(|d| d.as_millis() as u64),
+            last_success_seconds: status
+                .last_success
+                .map(|success| now.saturating_duration_since(success).as_secs()),
+            error: status
+                .circuit_open
+                .then(|| "circuit breaker is open".to_string()),
+        }
+    }
+
+    fn is_up(&self) -> bool {
+        self.status == "ok"
+    }
+
+    fn is_down(&self) -> bool {
+        self.status == "down"
+    }
+}
+
+// the process is running, nothing else is checked
+pub async fn live() -> impl Responder {
+    web::Json(HealthcheckResponse {
+        status: "ok".to_string(),
+    })
+}
+
+#[get("/health/ready")]
+async fn ready(providers: web::Data<Providers>) -> HttpResponse {
+    let start = Instant::now();
+    let ping = match timeout(PING_TIMEOUT, providers.moex.ping_cache()).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("ping timed out".to_string()),
+    };
+    let redis = Component {
+        status: if ping.is_ok() { "ok" } else { "down" },
+        latency_ms: Some(start.elapsed().as_millis() as u64),
+        last_success_seconds: None,
+        error: ping.err(),
+    };
+
+    let now = Instant::now();
+    let components = BTreeMap::from([
+        ("redis", redis),
+        (
+            "moex",
+            Component::upstream(providers.moex.upstream_status(), now),
+        ),
+        (
+            "spbex",
+            Component::upstream(providers.spbex.upstream_status(), now),
+        ),
+        (
+            "cbr",
+            Component::upstream(providers.cbr.upstream_status(), now),
+        ),
+    ]);
+    let response = ReadinessResponse {
+        status: summarize(&components),
+        components,
+    };
+    match response.status {
+        "unavailable" => HttpResponse::ServiceUnavailable().json(response),
+        _ => HttpResponse::Ok().json(response),
+    }
+}
+
+fn summarize(components: &BTreeMap<&'static str, Component>) -> &'static str {
+    let redis_up = components.get("redis").is_some_and(Component::is_up);
+    let (down, available): (Vec<_>, Vec<_>) = components
+        .iter()
+        .filter(|(name, _)| **name != "redis")
+        .partition(|(_, component)| component.is_down());
+    match (redis_up, available.is_empty(), down.is_empty()) {
+        (false, _, _) | (true, true, false) => "unavailable",
+        (true, _, true) => "ok",
+        (true, false, false) => "degraded",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn component(status: &'static str) -> Component {
+        Component {
+            status,
+            latency_ms: None,
+            last_success_seconds: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn summarize_pass() {
+        let components = |redis, moex, cbr| {
+            BTreeMap::from([
+                ("redis", component(redis)),
+                ("moex", component(moex)),
+                ("cbr", component(cbr)),
+            ])
+        };
+        assert_eq!(summarize(&components("ok", "ok", "ok")), "ok");
+        assert_eq!(summarize(&components("ok", "unknown", "ok")), "ok");
+        assert_eq!(summarize(&components("ok", "down", "unknown")), "degraded");
+        assert_eq!(summarize(&components("ok", "down", "ok")), "degraded");
+        assert_eq!(summarize(&components("ok", "down", "down")), "unavailable");
+        assert_eq!(summarize(&components("down", "ok", "ok")), "unavailable");
+    }
+
+    #[test]
+    fn component_pass_upstream() {
+        let now = Instant::now();
+        let status = UpstreamStatus {
+            last_success: Some(now - Duration::from_secs(10)),
+            last_duration: Some(Duration::from_millis(250)),
+            ..Default::default()
+        };
+        let result = Component::upstream(status, now);
+        assert!(result.is_up());
+        assert_eq!(result.latency_ms, Some(250));
+        assert_eq!(result.last_success_seconds, Some(10));
+
+        let open = UpstreamStatus {
+            circuit_open: true,
+            ..status
+        };
+        assert!(Component::upstream(open, now).is_down());
+
+        let idle = UpstreamStatus {
+            last_success: Some(now - UPSTREAM_WINDOW * 2),
+            ..Default::default()
+        };
+        assert_eq!(Component::upstream(idle, now).status, "unknown");
+    }
+}
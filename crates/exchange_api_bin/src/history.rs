@@ -0,0 +1,127 @@
+use actix_web::HttpResponse;
+use chrono::NaiveDate;
+use futures::future::join_all;
+use history_analytics::adjust;
+use history_analytics::resample::{Period, resample};
+use history_model::HistoryEntry;
+use serde::Deserialize;
+use std::error::Error;
+
+use crate::HealthcheckResponse;
+use crate::catalog::Catalog;
+use crate::currency::{self, Currency};
+use crate::providers::{Exchange, Providers, SeriesId};
+use crate::utils;
+
+pub const MAX_SERIES: usize = 20;
+
+// server side transformations shared by every history endpoint
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+pub struct HistoryQuery {
+    pub from: Option<NaiveDate>,
+    pub till: Option<NaiveDate>,
+    pub period: Option<Period>,
+    // days before the first cbr rate are left out of a converted history
+    pub currency: Option<Currency>,
+    pub adjust: Option<Adjust>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Adjust {
+    Splits,
+    // total return, splits are adjusted as well
+    Dividends,
+}
+
+impl HistoryQuery {
+    pub fn is_raw(&self) -> bool {
+        self.from.is_none()
+            && self.till.is_none()
+            && self.period.is_none()
+            && self.currency.is_none()
+            && self.adjust.is_none()
+    }
+}
+
+pub async fn load(
+    series: &SeriesId,
+    query: &HistoryQuery,
+    providers: &Providers,
+) -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
+    let history = match query.adjust {
+        Some(adjust) => adjusted_history(series, adjust, providers).await?,
+        None => providers.get_ticker(series).await?,
+    };
+    let mut history = utils::filter_dates(history, query.from, query.till);
+    if let Some(target) = query.currency {
+        let source = Currency::of_exchange(series.exchange);
+        history = currency::convert(history, source, target, providers).await?;
+    }
+    if let Some(period) = query.period {
+        history = resample(history, period);
+    }
+    Ok(history)
+}
+
+// corporate actions are only known for moex
+async fn adjusted_history(
+    series: &SeriesId,
+    adjust: Adjust,
+    providers: &Providers,
+) -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
+    if series.exchange != Exchange::Moex {
+        return Err(format!(
+            "adjustment is not available for {}",
+            series.exchange.as_str()
+        )
+        .into());
+    }
+
+    // prices and volumes before every split rescaled to the current share count
+    if adjust == Adjust::Splits {
+        let history = providers.moex.get_ticker(&series.ticker).await?;
+        let splits = providers.moex.get_splits(&series.ticker).await?;
+        return Ok(adjust::adjust_splits(history, &splits));
+    }
+
+    // dividends are per share at the time, so they go before the split adjustment
+    let history = providers.moex.get_ticker(&series.ticker).await?;
+    let dividends = providers.moex.get_dividends(&series.ticker).await?;
+    let dividends = currency::dividends_in_roubles(dividends, providers).await?;
+    let history = adjust::adjust_dividends(history, &dividends);
+    let splits = providers.moex.get_splits(&series.ticker).await?;
+    Ok(adjust::adjust_splits(history, &splits))
+}
+
+// all series or the first failure, fetched concurrently in the given order
+pub async fn load_all(
+    series: &[SeriesId],
+    query: &HistoryQuery,
+    providers: &Providers,
+) -> Result<Vec<Vec<HistoryEntry>>, Box<dyn Error>> {
+    join_all(series.iter().map(|id| async move {
+        load(id, query, providers)
+            .await
+            .map_err(|e| format!("{}: {}", id, e).into())
+    }))
+    .await
+    .into_iter()
+    .collect()
+}
+
+// a series the upstream failed to deliver, the same answer for every multi series endpoint
+pub fn load_failed(error: impl std::fmt::Display) -> HttpResponse {
+    HttpResponse::BadGateway().json(HealthcheckResponse {
+        status: error.to_string(),
+    })
+}
+
+// `series=` parameter of multi series endpoints
+pub fn parse_series_list(list: &str, catalog: &Catalog) -> Result<Vec<SeriesId>, Box<dyn Error>> {
+    let series = catalog.parse_list(list)?;
+    if series.is_empty() || series.len() > MAX_SERIES {
+        return Err(format!("expected 1 to {} series", MAX_SERIES).into());
+    }
+    Ok(series)
+}
@@ -0,0 +1,121 @@
+use actix_web::{HttpResponse, get, web};
+use chrono::NaiveDate;
+use history_analytics::indicators::{self, Band};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::HealthcheckResponse;
+use crate::history::{self, HistoryQuery};
+use crate::providers::{Exchange, Providers, SeriesId};
+
+const MAX_WINDOW: usize = 1000;
+const BOLLINGER_WIDTH: f64 = 2.0;
+
+#[derive(Debug, Deserialize)]
+pub struct IndicatorsQuery {
+    sma: Option<String>,
+    ema: Option<String>,
+    rsi: Option<String>,
+    bollinger: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IndicatorsResponse {
+    date: Vec<NaiveDate>,
+    close: Vec<f64>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    sma: BTreeMap<usize, Vec<Option<f64>>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    ema: BTreeMap<usize, Vec<Option<f64>>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    rsi: BTreeMap<usize, Vec<Option<f64>>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    bollinger: BTreeMap<usize, Vec<Option<Band>>>,
+}
+
+#[get("/{exchange}/{ticker}/indicators")]
+async fn get_indicators(
+    path: web::Path<(Exchange, String)>,
+    history_query: web::Query<HistoryQuery>,
+    query: web::Query<IndicatorsQuery>,
+    providers: web::Data<Providers>,
+) -> HttpResponse {
+    let windows = (
+        parse_windows(query.sma.as_deref()),
+        parse_windows(query.ema.as_deref()),
+        parse_windows(query.rsi.as_deref()),
+        parse_windows(query.bollinger.as_deref()),
+    );
+    let (Some(sma), Some(ema), Some(rsi), Some(bollinger)) = windows else {
+        return HttpResponse::BadRequest().json(HealthcheckResponse {
+            status: format!("invalid window, expected 1..={}", MAX_WINDOW),
+        });
+    };
+
+    let (exchange, ticker) = path.into_inner();
+    let series = SeriesId::new(exchange, &ticker);
+    let history = history::load(&series, &history_query, &providers)
+        .await
+        .unwrap_or_default();
+    let close: Vec<f64> = history.iter().map(|e| e.close).collect();
+
+    HttpResponse::Ok().json(IndicatorsResponse {
+        date: history.iter().map(|e| e.date).collect(),
+        sma: sma
+            .iter()
+            .map(|w| (*w, indicators::sma(&close, *w)))
+            .collect(),
+        ema: ema
+            .iter()
+            .map(|w| (*w, indicators::ema(&close, *w)))
+            .collect(),
+        rsi: rsi
+            .iter()
+            .map(|w| (*w, indicators::rsi(&close, *w)))
+            .collect(),
+        bollinger: bollinger
+            .iter()
+            .map(|w| (*w, indicators::bollinger(&close, *w, BOLLINGER_WIDTH)))
+            .collect(),
+        close,
+    })
+}
+
+// `20,50` into windows, None if any of them is not a usable window
+fn parse_windows(list: Option<&str>) -> Option<Vec<usize>> {
+    let Some(list) = list else {
+        return Some(vec![]);
+    };
+
+    list.split(',')
+        .filter(|w| !w.trim().is_empty())
+        .map(|w| {
+            w.trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|w| (1..=MAX_WINDOW).contains(w))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_windows_pass_missing() {
+        assert_eq!(parse_windows(None), Some(vec![]));
+    }
+
+    #[test]
+    fn parse_windows_pass_list() {
+        assert_eq!(parse_windows(Some("20, 50,")), Some(vec![20, 50]));
+    }
+
+    #[test]
+    fn parse_windows_fail_invalid() {
+        assert_eq!(parse_windows(Some("20,abc")), None);
+        assert_eq!(parse_windows(Some("0")), None);
+        assert_eq!(parse_windows(Some("1001")), None);
+    }
+}
@@ -1,16 +1,51 @@
+use api_config::{ConfigFile, ProviderSection, ServerConfig, UpstreamSection, env_opt, env_or};
 use cbr_api::api::CbrAPI;
 use dotenvy::dotenv;
-use log::{error, info};
+use futures::future::join_all;
 use moex_api::api::MoexAPI;
 use redis::ConnectionLike;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use spbex_api::api::SpbexAPI;
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use std::time::Duration;
 use std::{env, process::exit};
+use tracing::{error, info, warn};
+use upstream_client::UpstreamConfig;
 
-use actix_web::{App, HttpResponse, HttpServer, Responder, get, middleware::Logger, web};
+use actix_web::rt::signal::unix::{SignalKind, signal};
+use actix_web::{
+    App, HttpRequest, HttpResponse, HttpServer, Responder, get, middleware::from_fn, web,
+};
 
+use auth::Auth;
+use catalog::Catalog;
+use format::{Format, FormatQuery};
+use history::HistoryQuery;
 use history_model::HistoryEntry;
+use logging::LogFormat;
+use providers::{Exchange, Providers, SeriesId};
+use synthetic::Synthetics;
 
+mod auth;
+mod batch;
+mod catalog;
+mod columnar;
+mod compare;
+mod correlation;
+mod currency;
+mod format;
+mod health;
+mod history;
+mod indicators;
+mod logging;
+mod monitoring;
+mod portfolio;
+mod providers;
+mod stats;
+mod synthetic;
+mod telemetry;
+mod tls;
 mod utils;
 
 #[derive(Serialize)]
@@ -18,38 +53,111 @@ struct HealthcheckResponse {
     status: String,
 }
 
+#[derive(Deserialize)]
+struct ExportQuery {
+    series: String,
+    format: Option<Format>,
+}
+
 #[get("/moex/{ticker}")]
-async fn get_ticker_moex(ticker: web::Path<String>, api: web::Data<MoexAPI>) -> impl Responder {
-    let sanitized_ticker = utils::sanitize_ticker(ticker.to_string());
-    if let Ok(history) = api.get_ticker(&sanitized_ticker).await {
-        return web::Json(history);
-    }
-    web::Json(vec![])
+async fn get_ticker_moex(
+    req: HttpRequest,
+    ticker: web::Path<String>,
+    format_query: web::Query<FormatQuery>,
+    history_query: web::Query<HistoryQuery>,
+    providers: web::Data<Providers>,
+) -> HttpResponse {
+    let series = SeriesId::new(Exchange::Moex, &ticker);
+    respond_history(&req, &series, &format_query, &history_query, &providers).await
 }
 
 #[get("/spbex/{ticker}")]
-async fn get_ticker_spbex(ticker: web::Path<String>, api: web::Data<SpbexAPI>) -> impl Responder {
-    let sanitized_ticker = utils::sanitize_ticker(ticker.to_string());
-    if let Ok(history) = api.get_ticker(&sanitized_ticker).await {
-        return web::Json(history);
-    }
-    web::Json(vec![] as Vec<HistoryEntry>)
+async fn get_ticker_spbex(
+    req: HttpRequest,
+    ticker: web::Path<String>,
+    format_query: web::Query<FormatQuery>,
+    history_query: web::Query<HistoryQuery>,
+    providers: web::Data<Providers>,
+) -> HttpResponse {
+    let series = SeriesId::new(Exchange::Spbex, &ticker);
+    respond_history(&req, &series, &format_query, &history_query, &providers).await
 }
 
 #[get("/cbr/{ticker}")]
-async fn get_ticker_cbr(ticker: web::Path<String>, api: web::Data<CbrAPI>) -> impl Responder {
+async fn get_ticker_cbr(
+    req: HttpRequest,
+    ticker: web::Path<String>,
+    format_query: web::Query<FormatQuery>,
+    history_query: web::Query<HistoryQuery>,
+    providers: web::Data<Providers>,
+) -> HttpResponse {
+    let series = SeriesId::new(Exchange::Cbr, &ticker);
+    respond_history(&req, &series, &format_query, &history_query, &providers).await
+}
+
+async fn respond_history(
+    req: &HttpRequest,
+    series: &SeriesId,
+    format_query: &FormatQuery,
+    history_query: &HistoryQuery,
+    providers: &Providers,
+) -> HttpResponse {
+    // only raw moex history can be streamed, everything else needs the full series first
+    if series.exchange == Exchange::Moex
+        && history_query.is_raw()
+        && format::negotiate(req, format_query) == Format::Ndjson
+    {
+        return format::respond_stream(providers.moex.stream_ticker(&series.ticker)).await;
+    }
+
+    let history = history::load(series, history_query, providers)
+        .await
+        .unwrap_or_default();
+    format::respond(req, format_query, history)
+}
+
+#[get("/moex/{ticker}/splits")]
+async fn get_splits_moex(
+    ticker: web::Path<String>,
+    providers: web::Data<Providers>,
+) -> impl Responder {
     let sanitized_ticker = utils::sanitize_ticker(ticker.to_string());
-    if let Ok(history) = api.get_ticker(&sanitized_ticker).await {
-        return web::Json(history);
+    if let Ok(splits) = providers.moex.get_splits(&sanitized_ticker).await {
+        return web::Json(splits);
     }
     web::Json(vec![])
 }
 
-#[get("/healthcheck")]
-async fn healthcheck() -> impl Responder {
-    web::Json(HealthcheckResponse {
-        status: "ok".to_string(),
-    })
+#[get("/export")]
+async fn export(
+    query: web::Query<ExportQuery>,
+    providers: web::Data<Providers>,
+    catalog: web::Data<Catalog>,
+) -> HttpResponse {
+    let series = match history::parse_series_list(&query.series, &catalog) {
+        Ok(series) => series,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(HealthcheckResponse {
+                status: e.to_string(),
+            });
+        }
+    };
+
+    // a failed series would look like one without data, so the whole export fails instead
+    let histories = join_all(series.iter().map(|id| providers.get_ticker(id))).await;
+    let mut exported: Vec<(SeriesId, Vec<HistoryEntry>)> = Vec::with_capacity(series.len());
+    let mut errors = Vec::new();
+    for (id, history) in series.into_iter().zip(histories) {
+        match history {
+            Ok(history) => exported.push((id, history)),
+            Err(e) => errors.push(format!("{}: {}", id, e)),
+        }
+    }
+    if !errors.is_empty() {
+        return history::load_failed(errors.join("; "));
+    }
+
+    format::respond_export(query.format.unwrap_or(Format::Parquet), &exported)
 }
 
 async fn not_found() -> impl Responder {
@@ -59,34 +167,285 @@ async fn not_found() -> impl Responder {
 }
 
 struct Config {
+    // kept to tell what a reload changed
+    file: ConfigFile,
+    server: ServerConfig,
     workers: usize,
     redis_url: String,
+    actions_ttl: Option<Duration>,
+    synthetic: String,
+    otlp_endpoint: Option<String>,
+    log_format: LogFormat,
+    moex: ProviderConfig,
+    spbex: ProviderConfig,
+    cbr: ProviderConfig,
+    api_keys: Vec<String>,
+    // requests per minute, 0 is unlimited
+    key_rate_limit: u32,
+    ip_rate_limit: u32,
+    trust_forwarded: bool,
+}
+
+struct ProviderConfig {
+    // the provider default when unset
+    base_url: Option<String>,
+    upstream: UpstreamConfig,
 }
 
 impl Config {
+    // environment variables override the config file, defaults cover the rest
     fn new() -> Result<Config, Box<dyn std::error::Error>> {
         dotenv().ok();
 
-        let mut workers: usize = env::var("EXCHANGE_API_WORKERS")?.parse()?;
-        let mut redis_url = env::var("EXCHANGE_API_REDIS")?;
-
-        if workers == 0 {
-            workers = 1;
+        let file = ConfigFile::load()?;
+        let server = ServerConfig::load(&file.server)?;
+        let default_workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let workers = env_or(
+            "EXCHANGE_API_WORKERS",
+            file.server.workers.unwrap_or(default_workers),
+        )?
+        .max(1);
+        let redis_url = env_or(
+            "EXCHANGE_API_REDIS",
+            file.cache
+                .redis_url
+                .clone()
+                .unwrap_or_else(|| "redis://localhost:6379".to_string()),
+        )?;
+        let actions_ttl = env_opt(
+            "EXCHANGE_API_ACTIONS_TTL_SECONDS",
+            file.cache.actions_ttl_seconds,
+        )?
+        .map(Duration::from_secs);
+        // redis refuses SETEX with a zero expiry
+        if actions_ttl == Some(Duration::ZERO) {
+            return Err("actions_ttl_seconds must be greater than 0".into());
         }
-
-        if redis_url.trim().is_empty() {
-            redis_url = "redis://localhost:6379".to_string();
+        let synthetic = env::var("EXCHANGE_API_SYNTHETIC").unwrap_or_default();
+        // tracing is off unless a collector is configured
+        let otlp_endpoint = env::var("EXCHANGE_API_OTLP_ENDPOINT")
+            .ok()
+            .filter(|endpoint| !endpoint.trim().is_empty());
+        let log_format: LogFormat = env::var("EXCHANGE_API_LOG_FORMAT")
+            .unwrap_or_default()
+            .parse()?;
+        let upstream = upstream_config(&file.upstream)?;
+        let moex = provider_config("MOEX", &file.providers.moex, &upstream)?;
+        let spbex = provider_config("SPBEX", &file.providers.spbex, &upstream)?;
+        let cbr = provider_config("CBR", &file.providers.cbr, &upstream)?;
+        // no keys means no authentication
+        let api_keys = match env::var("EXCHANGE_API_KEYS") {
+            Ok(keys) if !keys.trim().is_empty() => keys
+                .split(',')
+                .map(|key| key.trim().to_string())
+                .filter(|key| !key.is_empty())
+                .collect(),
+            _ => file.auth.api_keys.clone().unwrap_or_default(),
+        };
+        let key_rate_limit = env_or(
+            "EXCHANGE_API_KEY_RATE_LIMIT",
+            file.auth.key_rate_limit.unwrap_or(0),
+        )?;
+        let ip_rate_limit = env_or(
+            "EXCHANGE_API_IP_RATE_LIMIT",
+            file.auth.ip_rate_limit.unwrap_or(0),
+        )?;
+        let trust_forwarded = env_or(
+            "EXCHANGE_API_TRUST_FORWARDED",
+            file.auth.trust_forwarded.unwrap_or(false),
+        )?;
+        // a unix socket has no peer address, every client would share one budget
+        if server.unix_socket.is_some() && ip_rate_limit > 0 && !trust_forwarded {
+            return Err("the per-ip rate limit on a unix socket needs trust_forwarded".into());
         }
 
-        let config = Config { workers, redis_url };
+        let config = Config {
+            file,
+            server,
+            workers,
+            redis_url,
+            actions_ttl,
+            synthetic,
+            otlp_endpoint,
+            log_format,
+            moex,
+            spbex,
+            cbr,
+            api_keys,
+            key_rate_limit,
+            ip_rate_limit,
+            trust_forwarded,
+        };
         Ok(config)
     }
 }
 
+// shared by the providers, re-read on SIGHUP
+fn upstream_config(file: &UpstreamSection) -> Result<UpstreamConfig, Box<dyn std::error::Error>> {
+    let defaults = UpstreamConfig::default();
+    Ok(UpstreamConfig {
+        connect_timeout: env_millis(
+            "EXCHANGE_API_UPSTREAM_CONNECT_TIMEOUT_MS",
+            file.connect_timeout_ms,
+            defaults.connect_timeout,
+        )?,
+        timeout: env_millis(
+            "EXCHANGE_API_UPSTREAM_TIMEOUT_MS",
+            file.timeout_ms,
+            defaults.timeout,
+        )?,
+        read_timeout: env_millis(
+            "EXCHANGE_API_UPSTREAM_READ_TIMEOUT_MS",
+            file.read_timeout_ms,
+            defaults.read_timeout,
+        )?,
+        retries: env_or(
+            "EXCHANGE_API_UPSTREAM_RETRIES",
+            file.retries.unwrap_or(defaults.retries),
+        )?,
+        backoff: env_millis(
+            "EXCHANGE_API_UPSTREAM_BACKOFF_MS",
+            file.backoff_ms,
+            defaults.backoff,
+        )?,
+        breaker_threshold: env_or(
+            "EXCHANGE_API_UPSTREAM_BREAKER_THRESHOLD",
+            file.breaker_threshold.unwrap_or(defaults.breaker_threshold),
+        )?,
+        breaker_cooldown: env_millis(
+            "EXCHANGE_API_UPSTREAM_BREAKER_COOLDOWN_MS",
+            file.breaker_cooldown_ms,
+            defaults.breaker_cooldown,
+        )?,
+        max_in_flight: env_or(
+            "EXCHANGE_API_UPSTREAM_MAX_IN_FLIGHT",
+            file.max_in_flight.unwrap_or(defaults.max_in_flight),
+        )?,
+        requests_per_second: env_or(
+            "EXCHANGE_API_UPSTREAM_RPS",
+            file.requests_per_second
+                .unwrap_or(defaults.requests_per_second),
+        )?,
+    })
+}
+
+// upstreams tolerate different load, e.g. `EXCHANGE_API_SPBEX_RPS=2`
+fn provider_config(
+    exchange: &str,
+    file: &ProviderSection,
+    upstream: &UpstreamConfig,
+) -> Result<ProviderConfig, Box<dyn std::error::Error>> {
+    let upstream = UpstreamConfig {
+        max_in_flight: env_or(
+            &format!("EXCHANGE_API_{}_MAX_IN_FLIGHT", exchange),
+            file.max_in_flight.unwrap_or(upstream.max_in_flight),
+        )?,
+        requests_per_second: env_or(
+            &format!("EXCHANGE_API_{}_RPS", exchange),
+            file.requests_per_second
+                .unwrap_or(upstream.requests_per_second),
+        )?,
+        ..upstream.clone()
+    };
+    upstream
+        .validate()
+        .map_err(|e| format!("{}: {}", exchange, e))?;
+    Ok(ProviderConfig {
+        base_url: env_opt(
+            &format!("EXCHANGE_API_{}_BASE_URL", exchange),
+            file.base_url.clone(),
+        )?,
+        upstream,
+    })
+}
+
+fn env_millis(
+    name: &str,
+    file: Option<u64>,
+    default: Duration,
+) -> Result<Duration, Box<dyn std::error::Error>> {
+    let millis = env_or(name, file.unwrap_or(default.as_millis() as u64))?;
+    Ok(Duration::from_millis(millis))
+}
+
+// SIGHUP re-reads the config file and the upstream variables. aliases, watchlists,
+// upstream timeouts, retries, the breaker and the provider limits apply right away.
+// server, cache, auth and base urls are read once at startup, the environment
+// variables of those included
+async fn reload_on_hangup(
+    startup: ConfigFile,
+    catalog: web::Data<Catalog>,
+    providers: web::Data<Providers>,
+) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            error!("Could not listen for SIGHUP: {}", e);
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        let file = match ConfigFile::load() {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Could not reload config: {}", e);
+                continue;
+            }
+        };
+        // validated as a whole, a bad value keeps every provider on the old settings
+        let upstream = upstream_config(&file.upstream).and_then(|upstream| {
+            Ok([
+                provider_config("MOEX", &file.providers.moex, &upstream)?,
+                provider_config("SPBEX", &file.providers.spbex, &upstream)?,
+                provider_config("CBR", &file.providers.cbr, &upstream)?,
+            ])
+        });
+        let [moex, spbex, cbr] = match upstream {
+            Ok(upstream) => upstream,
+            Err(e) => {
+                error!("Could not reload config: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = catalog.reload(&file) {
+            error!("Could not reload config: {}", e);
+            continue;
+        }
+        providers.moex.reconfigure(moex.upstream);
+        providers.spbex.reconfigure(spbex.upstream);
+        providers.cbr.reconfigure(cbr.upstream);
+        if !file.same_structure(&startup) {
+            warn!("Server, cache, auth and base url settings changed, they apply after a restart");
+        }
+        info!("Config reloaded");
+    }
+}
+
+// a socket left by a previous run makes the bind fail, other files are kept
+fn remove_stale_socket(path: &Path) -> std::io::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
-    let config = match Config::new() {
+    // logging comes first so configuration errors are reported in the configured format
+    let config = Config::new();
+    let (log_format, otlp_endpoint) = match &config {
+        Ok(config) => (config.log_format, config.otlp_endpoint.as_deref()),
+        Err(_) => (LogFormat::default(), None),
+    };
+    let tracer_provider = match telemetry::init(log_format, otlp_endpoint) {
+        Ok(provider) => provider,
+        Err(e) => {
+            eprintln!("Could not initialize logging: {}", e);
+            exit(1);
+        }
+    };
+
+    let config = match config {
         Ok(config) => config,
         Err(e) => {
             error!("Could not create config: {}", e);
@@ -94,6 +453,22 @@ async fn main() -> std::io::Result<()> {
         }
     };
 
+    let synthetics = match Synthetics::parse(&config.synthetic) {
+        Ok(synthetics) => web::Data::new(synthetics),
+        Err(e) => {
+            error!("Could not parse synthetic tickers: {}", e);
+            exit(1);
+        }
+    };
+
+    let metrics = match monitoring::install_recorder() {
+        Ok(handle) => web::Data::new(handle),
+        Err(e) => {
+            error!("Could not install metrics recorder: {}", e);
+            exit(1);
+        }
+    };
+
     let mut redis_client =
         redis::Client::open(config.redis_url).expect("Failed to create Redis client");
     let redis_connected = redis_client.check_connection();
@@ -103,24 +478,112 @@ async fn main() -> std::io::Result<()> {
     }
     info!("Redis connected");
 
-    let moex_api = web::Data::new(MoexAPI::new(redis_client));
-    let spbex_api = web::Data::new(SpbexAPI::new());
-    let cbr_api = web::Data::new(CbrAPI::new());
+    let mut moex = MoexAPI::with_config(redis_client, config.moex.upstream);
+    if let Some(base_url) = config.moex.base_url {
+        moex = moex.with_base_url(base_url);
+    }
+    if let Some(actions_ttl) = config.actions_ttl {
+        moex = moex.with_actions_ttl(actions_ttl);
+    }
+    let mut spbex = SpbexAPI::with_config(config.spbex.upstream);
+    if let Some(base_url) = config.spbex.base_url {
+        spbex = spbex.with_base_url(base_url);
+    }
+    let mut cbr = CbrAPI::with_config(config.cbr.upstream);
+    if let Some(base_url) = config.cbr.base_url {
+        cbr = cbr.with_base_url(base_url);
+    }
+    let providers = web::Data::new(Providers { moex, spbex, cbr });
+
+    let catalog = match Catalog::from_file(&config.file) {
+        Ok(catalog) => web::Data::new(catalog),
+        Err(e) => {
+            error!("Could not parse watchlists and aliases: {}", e);
+            exit(1);
+        }
+    };
+    if let Some(path) = ConfigFile::path() {
+        info!("Config loaded from {}", path.display());
+    }
+    actix_web::rt::spawn(reload_on_hangup(
+        config.file,
+        catalog.clone(),
+        providers.clone(),
+    ));
+
+    if config.api_keys.is_empty() && config.key_rate_limit > 0 {
+        warn!("API key rate limit is set without API keys and has no effect");
+    }
+    let auth = web::Data::new(Auth::new(
+        config.api_keys,
+        config.key_rate_limit,
+        config.ip_rate_limit,
+        config.trust_forwarded,
+    ));
 
-    HttpServer::new(move || {
+    let server = HttpServer::new(move || {
         App::new()
-            .app_data(moex_api.clone())
-            .app_data(spbex_api.clone())
-            .app_data(cbr_api.clone())
-            .service(healthcheck)
+            .app_data(providers.clone())
+            .app_data(synthetics.clone())
+            .app_data(metrics.clone())
+            .app_data(auth.clone())
+            .app_data(catalog.clone())
+            .route("/healthcheck", web::get().to(health::live))
+            .route("/health/live", web::get().to(health::live))
+            .service(health::ready)
+            .service(monitoring::get_metrics)
             .service(get_ticker_moex)
             .service(get_ticker_spbex)
             .service(get_ticker_cbr)
+            .service(get_splits_moex)
+            .service(export)
+            .service(batch::batch)
+            .service(compare::compare)
+            .service(correlation::get_correlation)
+            .service(indicators::get_indicators)
+            .service(stats::get_stats)
+            .service(synthetic::get_synthetic)
+            .service(synthetic::get_expression)
+            .service(portfolio::portfolio_value)
+            .service(catalog::get_watchlists)
             .default_service(web::to(not_found))
-            .wrap(Logger::default())
+            .wrap(from_fn(auth::authenticate))
+            .wrap(from_fn(monitoring::track_requests))
+            .wrap(from_fn(telemetry::trace_requests))
     })
-    .bind(("0.0.0.0", 8080))?
-    .workers(config.workers)
-    .run()
-    .await
+    .workers(config.workers);
+
+    let server = match (&config.server.unix_socket, &config.server.tls) {
+        (Some(path), _) => {
+            remove_stale_socket(path)?;
+            info!("Listening on {}", path.display());
+            server.bind_uds(path)?
+        }
+        (None, Some(tls)) => {
+            let tls_config = match tls::server_config(tls) {
+                Ok(tls_config) => tls_config,
+                Err(e) => {
+                    error!("Could not load TLS certificate: {}", e);
+                    exit(1);
+                }
+            };
+            let ServerConfig { host, port, .. } = &config.server;
+            info!("Listening on https://{}:{}", host, port);
+            server.bind_rustls_0_23((host.as_str(), *port), tls_config)?
+        }
+        (None, None) => {
+            let ServerConfig { host, port, .. } = &config.server;
+            info!("Listening on http://{}:{}", host, port);
+            server.bind((host.as_str(), *port))?
+        }
+    };
+    let result = server.run().await;
+
+    // flush spans still in the batch
+    if let Some(provider) = tracer_provider
+        && let Err(e) = provider.shutdown()
+    {
+        error!("Could not shut down tracing: {}", e);
+    }
+    result
 }
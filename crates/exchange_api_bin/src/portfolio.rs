@@ -0,0 +1,108 @@
+use actix_web::{HttpResponse, post, web};
+use chrono::NaiveDate;
+use history_analytics::portfolio::{self, Position, PositionValue, ValuePoint};
+use serde::{Deserialize, Serialize};
+
+use crate::HealthcheckResponse;
+use crate::currency::Currency;
+use crate::history::{self, HistoryQuery, MAX_SERIES};
+use crate::providers::{Exchange, Providers, SeriesId};
+
+#[derive(Debug, Deserialize)]
+pub struct Holding {
+    exchange: Exchange,
+    ticker: String,
+    quantity: f64,
+    purchase_date: Option<NaiveDate>,
+    // per unit in the portfolio currency
+    purchase_price: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PortfolioRequest {
+    holdings: Vec<Holding>,
+    currency: Option<Currency>,
+    from: Option<NaiveDate>,
+    till: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HoldingValue {
+    exchange: Exchange,
+    ticker: String,
+    quantity: f64,
+    #[serde(flatten)]
+    value: PositionValue,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PortfolioResponse {
+    currency: Currency,
+    value: f64,
+    cost: Option<f64>,
+    pnl: Option<f64>,
+    holdings: Vec<HoldingValue>,
+    series: Vec<ValuePoint>,
+}
+
+#[post("/portfolio/value")]
+async fn portfolio_value(
+    request: web::Json<PortfolioRequest>,
+    providers: web::Data<Providers>,
+) -> HttpResponse {
+    if request.holdings.is_empty() || request.holdings.len() > MAX_SERIES {
+        return HttpResponse::BadRequest().json(HealthcheckResponse {
+            status: format!("expected 1 to {} holdings", MAX_SERIES),
+        });
+    }
+
+    let currency = request.currency.unwrap_or(Currency::Rub);
+    let series: Vec<SeriesId> = request
+        .holdings
+        .iter()
+        .map(|h| SeriesId::new(h.exchange, &h.ticker))
+        .collect();
+    let query = HistoryQuery {
+        from: request.from,
+        till: request.till,
+        currency: Some(currency),
+        ..Default::default()
+    };
+
+    let prices = match history::load_all(&series, &query, &providers).await {
+        Ok(prices) => prices,
+        Err(e) => return history::load_failed(e),
+    };
+
+    let positions: Vec<Position> = request
+        .holdings
+        .iter()
+        .map(|h| Position {
+            quantity: h.quantity,
+            purchase_date: h.purchase_date,
+            purchase_price: h.purchase_price,
+        })
+        .collect();
+    let valuation = portfolio::value(&positions, &prices);
+
+    let holdings = series
+        .into_iter()
+        .zip(&request.holdings)
+        .zip(valuation.positions)
+        .map(|((id, holding), value)| HoldingValue {
+            exchange: id.exchange,
+            ticker: id.ticker,
+            quantity: holding.quantity,
+            value,
+        })
+        .collect();
+
+    HttpResponse::Ok().json(PortfolioResponse {
+        currency,
+        value: valuation.value,
+        cost: valuation.cost,
+        pnl: valuation.pnl,
+        holdings,
+        series: valuation.series,
+    })
+}
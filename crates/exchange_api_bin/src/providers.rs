@@ -0,0 +1,133 @@
+use cbr_api::api::CbrAPI;
+use history_model::HistoryEntry;
+use moex_api::api::MoexAPI;
+use serde::{Deserialize, Serialize};
+use spbex_api::api::SpbexAPI;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::utils;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Exchange {
+    Moex,
+    Spbex,
+    Cbr,
+}
+
+impl Exchange {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Exchange::Moex => "moex",
+            Exchange::Spbex => "spbex",
+            Exchange::Cbr => "cbr",
+        }
+    }
+}
+
+impl FromStr for Exchange {
+    type Err = ParseSeriesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "moex" => Ok(Exchange::Moex),
+            "spbex" => Ok(Exchange::Spbex),
+            "cbr" => Ok(Exchange::Cbr),
+            _ => Err(ParseSeriesError(s.to_string())),
+        }
+    }
+}
+
+// exchange and sanitized ticker, written as `moex:sber` in query strings
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SeriesId {
+    pub exchange: Exchange,
+    pub ticker: String,
+}
+
+impl SeriesId {
+    pub fn new(exchange: Exchange, ticker: &str) -> Self {
+        SeriesId {
+            exchange,
+            ticker: utils::sanitize_ticker(ticker.to_string()),
+        }
+    }
+}
+
+impl FromStr for SeriesId {
+    type Err = ParseSeriesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (exchange, ticker) = s
+            .split_once(':')
+            .ok_or_else(|| ParseSeriesError(s.to_string()))?;
+        let series = SeriesId::new(exchange.parse()?, ticker.trim());
+        if series.ticker.is_empty() {
+            return Err(ParseSeriesError(s.to_string()));
+        }
+        Ok(series)
+    }
+}
+
+impl fmt::Display for SeriesId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.exchange.as_str(), self.ticker)
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseSeriesError(String);
+
+impl fmt::Display for ParseSeriesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid series: {}", self.0)
+    }
+}
+
+impl Error for ParseSeriesError {}
+
+pub struct Providers {
+    pub moex: MoexAPI,
+    pub spbex: SpbexAPI,
+    pub cbr: CbrAPI,
+}
+
+impl Providers {
+    pub async fn get_ticker(&self, series: &SeriesId) -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
+        match series.exchange {
+            Exchange::Moex => self.moex.get_ticker(&series.ticker).await,
+            Exchange::Spbex => self.spbex.get_ticker(&series.ticker).await,
+            Exchange::Cbr => self.cbr.get_ticker(&series.ticker).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn series_id_pass_parse() {
+        let result: SeriesId = "MOEX:SBER".parse().unwrap();
+        assert_eq!(result, SeriesId::new(Exchange::Moex, "sber"));
+    }
+
+    #[test]
+    fn series_id_pass_display() {
+        let result = SeriesId::new(Exchange::Cbr, "usd").to_string();
+        assert_eq!(result, "cbr:usd".to_string());
+    }
+
+    #[test]
+    fn series_id_fail_unknown_exchange() {
+        assert!("nyse:aapl".parse::<SeriesId>().is_err());
+    }
+
+    #[test]
+    fn series_id_fail_no_ticker() {
+        assert!("moex".parse::<SeriesId>().is_err());
+        assert!("moex:..".parse::<SeriesId>().is_err());
+    }
+}
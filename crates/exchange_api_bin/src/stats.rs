@@ -0,0 +1,34 @@
+use actix_web::{HttpResponse, get, web};
+use history_analytics::stats;
+use serde::Deserialize;
+
+use crate::HealthcheckResponse;
+use crate::history::{self, HistoryQuery};
+use crate::providers::{Exchange, Providers, SeriesId};
+
+#[derive(Debug, Deserialize)]
+pub struct StatsQuery {
+    // annual rate as a fraction, 0.16 for 16%
+    risk_free: Option<f64>,
+}
+
+#[get("/{exchange}/{ticker}/stats")]
+async fn get_stats(
+    path: web::Path<(Exchange, String)>,
+    history_query: web::Query<HistoryQuery>,
+    query: web::Query<StatsQuery>,
+    providers: web::Data<Providers>,
+) -> HttpResponse {
+    let (exchange, ticker) = path.into_inner();
+    let series = SeriesId::new(exchange, &ticker);
+    let history = history::load(&series, &history_query, &providers)
+        .await
+        .unwrap_or_default();
+
+    match stats::stats(&history, query.risk_free.unwrap_or_default()) {
+        Some(stats) => HttpResponse::Ok().json(stats),
+        None => HttpResponse::NotFound().json(HealthcheckResponse {
+            status: "not enough data".to_string(),
+        }),
+    }
+}
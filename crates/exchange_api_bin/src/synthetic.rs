@@ -0,0 +1,187 @@
+use actix_web::{HttpRequest, HttpResponse, get, web};
+use history_analytics::align::{self, Fill};
+use history_analytics::expression::Expr;
+use history_analytics::resample::resample;
+use history_model::HistoryEntry;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::HealthcheckResponse;
+use crate::format::{self, FormatQuery};
+use crate::history::{self, HistoryQuery, MAX_SERIES};
+use crate::providers::{Providers, SeriesId};
+use crate::utils;
+
+// named expressions served under /synthetic/{name}
+#[derive(Debug, Default)]
+pub struct Synthetics {
+    definitions: HashMap<String, Expr>,
+}
+
+impl Synthetics {
+    // `imoex_usd=moex:imoex/cbr:usd;spread=moex:a-moex:b`
+    pub fn parse(definitions: &str) -> Result<Synthetics, Box<dyn Error>> {
+        let mut synthetics = Synthetics::default();
+        for definition in definitions.split(';').filter(|d| !d.trim().is_empty()) {
+            let (name, expr) = definition
+                .split_once('=')
+                .ok_or_else(|| format!("invalid synthetic definition: {}", definition))?;
+            synthetics.insert(name, expr)?;
+        }
+        Ok(synthetics)
+    }
+
+    pub fn insert(&mut self, name: &str, expr: &str) -> Result<(), Box<dyn Error>> {
+        let expr = Expr::parse(expr).map_err(|e| format!("synthetic {}: {}", name, e))?;
+        self.definitions
+            .insert(utils::sanitize_ticker(name.to_string()), expr);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Expr> {
+        self.definitions.get(name)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExpressionQuery {
+    expr: String,
+}
+
+#[get("/synthetic/{name}")]
+async fn get_synthetic(
+    req: HttpRequest,
+    name: web::Path<String>,
+    format_query: web::Query<FormatQuery>,
+    history_query: web::Query<HistoryQuery>,
+    synthetics: web::Data<Synthetics>,
+    providers: web::Data<Providers>,
+) -> HttpResponse {
+    let name = utils::sanitize_ticker(name.to_string());
+    let Some(expr) = synthetics.get(&name) else {
+        return HttpResponse::NotFound().json(HealthcheckResponse {
+            status: "not found".to_string(),
+        });
+    };
+    respond_expression(&req, expr, &format_query, &history_query, &providers).await
+}
+
+#[get("/synthetic")]
+async fn get_expression(
+    req: HttpRequest,
+    query: web::Query<ExpressionQuery>,
+    format_query: web::Query<FormatQuery>,
+    history_query: web::Query<HistoryQuery>,
+    providers: web::Data<Providers>,
+) -> HttpResponse {
+    match Expr::parse(&query.expr) {
+        Ok(expr) => {
+            respond_expression(&req, &expr, &format_query, &history_query, &providers).await
+        }
+        Err(e) => HttpResponse::BadRequest().json(HealthcheckResponse {
+            status: e.to_string(),
+        }),
+    }
+}
+
+async fn respond_expression(
+    req: &HttpRequest,
+    expr: &Expr,
+    format_query: &FormatQuery,
+    history_query: &HistoryQuery,
+    providers: &Providers,
+) -> HttpResponse {
+    let series: Result<Vec<SeriesId>, _> = expr.symbols().iter().map(|s| s.parse()).collect();
+    let series = match series {
+        Ok(series) if series.len() <= MAX_SERIES => series,
+        Ok(_) => {
+            return HttpResponse::BadRequest().json(HealthcheckResponse {
+                status: format!("expected up to {} series", MAX_SERIES),
+            });
+        }
+        Err(e) => {
+            return HttpResponse::BadRequest().json(HealthcheckResponse {
+                status: e.to_string(),
+            });
+        }
+    };
+
+    match evaluate(expr, &series, history_query, providers).await {
+        Ok(history) => format::respond(req, format_query, history),
+        Err(e) => HttpResponse::NotFound().json(HealthcheckResponse {
+            status: e.to_string(),
+        }),
+    }
+}
+
+// evaluates the expression on forward filled closes of its series, dates where
+// any series has not started yet or the result is undefined are skipped
+async fn evaluate(
+    expr: &Expr,
+    series: &[SeriesId],
+    query: &HistoryQuery,
+    providers: &Providers,
+) -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
+    // legs are combined daily, the period applies to the synthetic series
+    let legs_query = HistoryQuery {
+        period: None,
+        ..*query
+    };
+    let legs = history::load_all(series, &legs_query, providers).await?;
+    let symbols = expr.symbols();
+
+    let history: Vec<HistoryEntry> = align::align(&legs, Fill::Ffill)
+        .into_iter()
+        .filter_map(|row| {
+            let value = expr.eval(&|name| {
+                let column = symbols.iter().position(|s| *s == name)?;
+                row.values[column]
+            })?;
+            Some(HistoryEntry {
+                date: row.date,
+                open: value,
+                close: value,
+                high: value,
+                low: value,
+                volume: 0,
+                facevalue: 1,
+            })
+        })
+        .collect();
+
+    Ok(match query.period {
+        Some(period) => resample(history, period),
+        None => history,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synthetics_pass_parse() {
+        let result =
+            Synthetics::parse("IMOEX_USD=moex:imoex / cbr:usd; spread = moex:a-moex:b;").unwrap();
+        assert_eq!(
+            result.get("imoex_usd").unwrap().symbols(),
+            vec!["moex:imoex", "cbr:usd"]
+        );
+        assert_eq!(
+            result.get("spread").unwrap().symbols(),
+            vec!["moex:a", "moex:b"]
+        );
+    }
+
+    #[test]
+    fn synthetics_pass_empty() {
+        assert!(Synthetics::parse("").unwrap().definitions.is_empty());
+    }
+
+    #[test]
+    fn synthetics_fail_invalid() {
+        assert!(Synthetics::parse("no_expression").is_err());
+        assert!(Synthetics::parse("bad=moex:a +").is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use opentelemetry::global;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::{TraceContextExt, TraceId, TracerProvider};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use std::error::Error;
+use std::io::{self, IsTerminal};
+use std::time::Instant;
+use tracing::{Instrument, field, info, info_span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::fmt::format::JsonFields;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer, fmt};
+
+use crate::logging::{self, JsonFormat, LogFormat, REQUEST_ID_HEADER};
+
+const SERVICE_NAME: &str = "exchange_api";
+pub const TRACE_ID_HEADER: &str = "x-trace-id";
+
+// logs go to stderr filtered by RUST_LOG (info by default), `log` crate records included.
+// spans are exported only when an otlp endpoint is given
+pub fn init(
+    log_format: LogFormat,
+    otlp_endpoint: Option<&str>,
+) -> Result<Option<SdkTracerProvider>, Box<dyn Error>> {
+    let log_layer = match log_format {
+        LogFormat::Text => fmt::layer()
+            .with_writer(io::stderr)
+            .with_ansi(io::stderr().is_terminal())
+            .boxed(),
+        LogFormat::Json => fmt::layer()
+            .with_writer(io::stderr)
+            .fmt_fields(JsonFields::new())
+            .event_format(JsonFormat)
+            .boxed(),
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+
+    let provider = otlp_endpoint.map(tracer_provider).transpose()?;
+    let otel_layer = provider
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME)));
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    tracing_subscriber::registry()
+        .with(log_layer)
+        .with(otel_layer)
+        .with(filter)
+        .try_init()?;
+
+    Ok(provider)
+}
+
+// the endpoint is the full traces url, e.g. http://localhost:4318/v1/traces
+// for a local collector
+fn tracer_provider(endpoint: &str) -> Result<SdkTracerProvider, Box<dyn Error>> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()?;
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+        .build())
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}
+
+// root span of a request, continues an incoming `traceparent`. the request id is
+// returned in every response, the trace id only when spans are exported
+pub async fn trace_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(req.headers()))
+    });
+    let request_id = logging::request_id(req.headers());
+    let method = req.method().to_string();
+    let span = info_span!(
+        "http request",
+        otel.kind = "server",
+        otel.name = field::Empty,
+        request_id = %request_id,
+        http.request.method = %method,
+        url.path = %req.path(),
+        http.route = field::Empty,
+        http.response.status_code = field::Empty,
+    );
+    let _ = span.set_parent(parent);
+
+    let path = req.path().to_string();
+    let start = Instant::now();
+    let result = next.call(req).instrument(span.clone()).await;
+    let duration_ms = start.elapsed().as_millis() as u64;
+    // an error from an inner middleware still gets its access line
+    let mut response = match result {
+        Ok(response) => response,
+        Err(e) => {
+            let status = e.as_response_error().status_code().as_u16();
+            span.record("http.response.status_code", status);
+            span.in_scope(|| info!(status, duration_ms, "{} {}", method, path));
+            return Err(e);
+        }
+    };
+
+    let request = response.request();
+    let route = request
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+    let status = response.status().as_u16();
+    span.record("otel.name", format!("{} {}", method, route));
+    span.record("http.route", &route);
+    span.record("http.response.status_code", status);
+
+    // `/moex/{ticker}` has the exchange as a literal segment
+    let ticker = request.match_info().get("ticker");
+    let exchange = request
+        .match_info()
+        .get("exchange")
+        .or_else(|| ticker.and(request.path().split('/').nth(1)));
+    span.in_scope(|| {
+        info!(
+            exchange,
+            ticker,
+            status,
+            duration_ms,
+            peer = request.connection_info().realip_remote_addr(),
+            "{} {}",
+            method,
+            path
+        )
+    });
+
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        headers.insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+    let trace_id = span.context().span().span_context().trace_id();
+    if trace_id != TraceId::INVALID
+        && let Ok(value) = HeaderValue::from_str(&trace_id.to_string())
+    {
+        headers.insert(HeaderName::from_static(TRACE_ID_HEADER), value);
+    }
+
+    Ok(response)
+}
@@ -1,16 +1,44 @@
+use chrono::NaiveDate;
+use history_model::HistoryEntry;
+
 pub fn sanitize_ticker(ticker: String) -> String {
-    return ticker
+    ticker
         .chars()
         .take(20)
         .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
         .collect::<String>()
-        .to_lowercase();
+        .to_lowercase()
+}
+
+// keeps entries within the inclusive [from, till] range, open ends are unbounded
+pub fn filter_dates(
+    history: Vec<HistoryEntry>,
+    from: Option<NaiveDate>,
+    till: Option<NaiveDate>,
+) -> Vec<HistoryEntry> {
+    history
+        .into_iter()
+        .filter(|e| from.is_none_or(|from| e.date >= from))
+        .filter(|e| till.is_none_or(|till| e.date <= till))
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn entry(date: &str) -> HistoryEntry {
+        HistoryEntry {
+            date: date.parse().unwrap(),
+            open: 1.0,
+            close: 1.0,
+            high: 1.0,
+            low: 1.0,
+            volume: 0,
+            facevalue: 1,
+        }
+    }
+
     #[test]
     fn sanitize_ticker_pass_no_harm() {
         let result = sanitize_ticker("123".to_string());
@@ -46,4 +74,27 @@ mod tests {
         let result = sanitize_ticker("AaAa".to_string());
         assert_ne!(result, "AaAa".to_string());
     }
+
+    #[test]
+    fn filter_dates_pass_open_range() {
+        let history = vec![entry("2024-01-01"), entry("2024-01-02")];
+        let result = filter_dates(history.clone(), None, None);
+        assert_eq!(result, history);
+    }
+
+    #[test]
+    fn filter_dates_pass_inclusive() {
+        let history = vec![
+            entry("2024-01-01"),
+            entry("2024-01-02"),
+            entry("2024-01-03"),
+            entry("2024-01-04"),
+        ];
+        let result = filter_dates(
+            history,
+            Some("2024-01-02".parse().unwrap()),
+            Some("2024-01-03".parse().unwrap()),
+        );
+        assert_eq!(result, vec![entry("2024-01-02"), entry("2024-01-03")]);
+    }
 }
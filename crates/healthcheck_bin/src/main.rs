@@ -1,10 +1,25 @@
-use reqwest;
+use api_config::{ConfigFile, ServerConfig, env_opt, env_or};
+use chrono::{Duration as Days, Local, NaiveDate};
+use dotenvy::dotenv;
+use history_model::HistoryEntry;
+use reqwest::blocking::Client;
 use serde::Deserialize;
+use std::process::ExitCode;
+use std::time::Duration;
 
+const USAGE: &str = "usage: healthcheck [--live | --ready] [--url URL] [--timeout-ms MS] \
+                     [--ticker EXCHANGE:TICKER] [--max-age-days DAYS] [--api-key KEY]";
+// ten years, far beyond any holiday gap
+const MAX_AGE_DAYS: i64 = 3650;
+
+// the exit code tells a supervisor what went wrong
 #[derive(Debug)]
 enum CustomError {
-    ReqwestError(String),
-    NotOk,
+    Config(String),
+    // no answer in time, refused connection, tls
+    Connection(String),
+    Status(u16),
+    Body(String),
 }
 
 #[derive(Debug, Deserialize)]
@@ -12,29 +27,334 @@ struct StatusJSON {
     status: String,
 }
 
+// `--live` (default) or `--ready`, which also checks redis and the upstreams
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Probe {
+    Live,
+    Ready,
+}
+
+impl Probe {
+    fn path(self) -> &'static str {
+        match self {
+            Probe::Live => "/health/live",
+            Probe::Ready => "/health/ready",
+        }
+    }
+
+    // a degraded server still serves the providers that are up
+    fn accepts(self, status: &str) -> bool {
+        match self {
+            Probe::Live => status == "ok",
+            Probe::Ready => status == "ok" || status == "degraded",
+        }
+    }
+}
+
+// flags take precedence over EXCHANGE_API_HEALTHCHECK_* variables
+#[derive(Debug, PartialEq)]
+struct Options {
+    probe: Probe,
+    // scheme, host and port, derived from the server config when unset
+    url: Option<String>,
+    timeout: Duration,
+    // `moex:sber`, fetched end to end after the probe when set
+    ticker: Option<String>,
+    max_age_days: i64,
+    api_key: Option<String>,
+}
+
+impl Options {
+    fn from_env() -> Result<Options, CustomError> {
+        let config = |e: Box<dyn std::error::Error>| CustomError::Config(e.to_string());
+        Ok(Options {
+            probe: Probe::Live,
+            url: env_opt("EXCHANGE_API_HEALTHCHECK_URL", None).map_err(config)?,
+            timeout: Duration::from_millis(
+                env_or("EXCHANGE_API_HEALTHCHECK_TIMEOUT_MS", 5000).map_err(config)?,
+            ),
+            ticker: env_opt("EXCHANGE_API_HEALTHCHECK_TICKER", None).map_err(config)?,
+            max_age_days: env_or("EXCHANGE_API_HEALTHCHECK_MAX_AGE_DAYS", 7).map_err(config)?,
+            api_key: env_opt("EXCHANGE_API_HEALTHCHECK_KEY", None).map_err(config)?,
+        })
+    }
+
+    fn parse_args(
+        mut self,
+        args: impl IntoIterator<Item = String>,
+    ) -> Result<Options, CustomError> {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| CustomError::Config(format!("{} needs a value", flag)))
+            };
+            match flag.as_str() {
+                "--live" => self.probe = Probe::Live,
+                "--ready" => self.probe = Probe::Ready,
+                "--url" => self.url = Some(value()?),
+                "--timeout-ms" => self.timeout = Duration::from_millis(parse(&flag, value()?)?),
+                "--ticker" => self.ticker = Some(value()?),
+                "--max-age-days" => self.max_age_days = parse(&flag, value()?)?,
+                "--api-key" => self.api_key = Some(value()?),
+                _ => {
+                    return Err(CustomError::Config(format!(
+                        "unknown argument {}\n{}",
+                        flag, USAGE
+                    )));
+                }
+            }
+        }
+        self.validate()?;
+        Ok(self)
+    }
+
+    // covers the environment values as well, flags are parsed after them
+    fn validate(&self) -> Result<(), CustomError> {
+        if self.timeout.is_zero() {
+            return Err(CustomError::Config("timeout must be positive".to_string()));
+        }
+        if !(0..=MAX_AGE_DAYS).contains(&self.max_age_days) {
+            return Err(CustomError::Config(format!(
+                "max age must be between 0 and {} days",
+                MAX_AGE_DAYS
+            )));
+        }
+        if let Some(ticker) = &self.ticker
+            && split_ticker(ticker).is_none()
+        {
+            return Err(invalid_ticker(ticker));
+        }
+        Ok(())
+    }
+}
+
+// `exchange:ticker`, both parts present
+fn split_ticker(ticker: &str) -> Option<(&str, &str)> {
+    ticker
+        .split_once(':')
+        .filter(|(exchange, ticker)| !exchange.is_empty() && !ticker.is_empty())
+}
+
+fn invalid_ticker(ticker: &str) -> CustomError {
+    CustomError::Config(format!(
+        "invalid ticker {}, expected EXCHANGE:TICKER",
+        ticker
+    ))
+}
+
+fn parse<T: std::str::FromStr>(flag: &str, value: String) -> Result<T, CustomError> {
+    value
+        .parse()
+        .map_err(|_| CustomError::Config(format!("invalid {} value {}", flag, value)))
+}
+
+impl CustomError {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            CustomError::Config(_) => ExitCode::from(1),
+            CustomError::Connection(_) => ExitCode::from(2),
+            CustomError::Status(_) => ExitCode::from(3),
+            CustomError::Body(_) => ExitCode::from(4),
+        }
+    }
+}
+
 impl std::fmt::Display for CustomError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            CustomError::ReqwestError(e) => write!(f, "Reqwest error: {}", e),
-            CustomError::NotOk => write!(f, "Status code != 200 or no healthcheck"),
+            CustomError::Config(e) => write!(f, "Config error: {}", e),
+            CustomError::Connection(e) => write!(f, "Connection error: {}", e),
+            CustomError::Status(status) => write!(f, "Unexpected status code {}", status),
+            CustomError::Body(e) => write!(f, "Unexpected body: {}", e),
         }
     }
 }
 
 impl From<reqwest::Error> for CustomError {
     fn from(err: reqwest::Error) -> CustomError {
-        CustomError::ReqwestError(err.to_string())
+        // a body cut off by the timeout is a connection problem, not a bad body
+        if !err.is_timeout() && (err.is_decode() || err.is_body()) {
+            return CustomError::Body(err.to_string());
+        }
+        CustomError::Connection(err.to_string())
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            e.exit_code()
+        }
     }
 }
 
-fn main() -> Result<(), CustomError> {
-    let res = reqwest::blocking::get("http://localhost:8080/healthcheck")?;
+fn run() -> Result<(), CustomError> {
+    // same settings as the server, so the probe follows its address
+    dotenv().ok();
+    let options = Options::from_env()?.parse_args(std::env::args().skip(1))?;
+    let config = ConfigFile::load()
+        .and_then(|file| ServerConfig::load(&file.server))
+        .map_err(|e| CustomError::Config(e.to_string()))?;
+
+    // the timeout covers the whole request, a hung server fails the check
+    let mut client = Client::builder().timeout(options.timeout);
+    if options.url.is_none() {
+        if let Some(path) = &config.unix_socket {
+            client = client.unix_socket(path.as_path());
+        }
+        if config.tls.is_some() {
+            // the server is probed locally, its certificate is issued for the public name
+            client = client.danger_accept_invalid_certs(true);
+        }
+    }
+    let client = client.build()?;
+    let url = |path: &str| match &options.url {
+        Some(base) => format!("{}{}", base.trim_end_matches('/'), path),
+        None => config.local_url(path),
+    };
+
+    let res = client.get(url(options.probe.path())).send()?;
     if res.status() != 200 {
-        return Err(CustomError::NotOk);
+        return Err(CustomError::Status(res.status().as_u16()));
     }
     let ok_str: StatusJSON = res.json::<StatusJSON>()?;
-    if ok_str.status != "ok" {
-        return Err(CustomError::NotOk);
+    if !options.probe.accepts(&ok_str.status) {
+        return Err(CustomError::Body(format!("status {}", ok_str.status)));
+    }
+
+    if let Some(ticker) = &options.ticker {
+        let (exchange, ticker) = split_ticker(ticker).ok_or_else(|| invalid_ticker(ticker))?;
+        let mut req = client
+            .get(url(&format!("/{}/{}", exchange, ticker)))
+            .header(reqwest::header::ACCEPT, "application/json");
+        if let Some(key) = &options.api_key {
+            req = req.header("x-api-key", key);
+        }
+        let res = req.send()?;
+        if res.status() != 200 {
+            return Err(CustomError::Status(res.status().as_u16()));
+        }
+        let history: Vec<HistoryEntry> = res.json()?;
+        let today = Local::now().date_naive();
+        check_history(&history, today, options.max_age_days)?;
+    }
+    Ok(())
+}
+
+// weekends and holidays have no entries, the allowed age covers them
+fn check_history(
+    history: &[HistoryEntry],
+    today: NaiveDate,
+    max_age_days: i64,
+) -> Result<(), CustomError> {
+    let Some(latest) = history.iter().map(|entry| entry.date).max() else {
+        return Err(CustomError::Body("empty history".to_string()));
+    };
+    if today - latest > Days::days(max_age_days) {
+        return Err(CustomError::Body(format!(
+            "latest entry is from {}",
+            latest
+        )));
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> Options {
+        Options {
+            probe: Probe::Live,
+            url: None,
+            timeout: Duration::from_secs(5),
+            ticker: None,
+            max_age_days: 7,
+            api_key: None,
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn entry(date: &str) -> HistoryEntry {
+        HistoryEntry {
+            date: date.parse().unwrap(),
+            open: 1.0,
+            close: 1.0,
+            high: 1.0,
+            low: 1.0,
+            volume: 0,
+            facevalue: 1,
+        }
+    }
+
+    #[test]
+    fn parse_args_pass() {
+        let result = options()
+            .parse_args(args(&[
+                "--ready",
+                "--url=http://api:8080/",
+                "--timeout-ms",
+                "2000",
+                "--ticker",
+                "moex:sber",
+                "--api-key=secret",
+            ]))
+            .unwrap();
+        assert_eq!(result.probe, Probe::Ready);
+        assert_eq!(result.url.as_deref(), Some("http://api:8080/"));
+        assert_eq!(result.timeout, Duration::from_secs(2));
+        assert_eq!(result.ticker.as_deref(), Some("moex:sber"));
+        assert_eq!(result.api_key.as_deref(), Some("secret"));
+    }
+
+    #[test]
+    fn parse_args_fail() {
+        assert!(options().parse_args(args(&["--bogus"])).is_err());
+        assert!(options().parse_args(args(&["--timeout-ms"])).is_err());
+        assert!(
+            options()
+                .parse_args(args(&["--timeout-ms", "soon"]))
+                .is_err()
+        );
+        assert!(options().parse_args(args(&["--timeout-ms", "0"])).is_err());
+        assert!(options().parse_args(args(&["--max-age-days=-1"])).is_err());
+        assert!(options().parse_args(args(&["--ticker", "sber"])).is_err());
+        assert!(options().parse_args(args(&["--ticker=moex:"])).is_err());
+        assert!(
+            options()
+                .parse_args(args(&["--max-age-days", "9223372036854775807"]))
+                .is_err()
+        );
+        let from_env = Options {
+            max_age_days: -3,
+            ..options()
+        };
+        assert!(from_env.parse_args(args(&[])).is_err());
+    }
+
+    #[test]
+    fn check_history_pass() {
+        let today = "2024-03-11".parse().unwrap();
+        let history = [entry("2024-03-07"), entry("2024-03-08")];
+        assert!(check_history(&history, today, 7).is_ok());
+    }
+
+    #[test]
+    fn check_history_fail() {
+        let today = "2024-03-11".parse().unwrap();
+        assert!(check_history(&[], today, 7).is_err());
+        assert!(check_history(&[entry("2024-02-01")], today, 7).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use chrono::NaiveDate;
+use history_model::{Dividend, HistoryEntry, Split};
+
+// first registry close date settled T+1 on moex, T+2 before
+const T1_SETTLEMENT_FROM: NaiveDate = NaiveDate::from_ymd_opt(2023, 7, 31).unwrap();
+
+// backward adjustment: the latest prices stay as traded and earlier ones are scaled
+// so that returns across dividends and splits are continuous
+
+// total return adjustment. with T+1 settlement the first trading day on or after
+// the registry close date is the ex-dividend day, with T+2 the trading day before it.
+// every price before the ex-dividend day is scaled by 1 - dividend / close of the
+// last cum-dividend day. dividends are expected in the currency of the prices
+pub fn adjust_dividends(history: Vec<HistoryEntry>, dividends: &[Dividend]) -> Vec<HistoryEntry> {
+    let mut factors = vec![1.0; history.len()];
+    for dividend in dividends {
+        let on_or_after = history.partition_point(|e| e.date < dividend.date);
+        // the close date is past the history, the ex-dividend day may not be known yet
+        if on_or_after == history.len() {
+            continue;
+        }
+        let ex = match dividend.date < T1_SETTLEMENT_FROM {
+            true => on_or_after.saturating_sub(1),
+            false => on_or_after,
+        };
+        if ex == 0 {
+            continue;
+        }
+        let cum_close = history[ex - 1].close;
+        if cum_close <= 0.0 || dividend.value >= cum_close {
+            continue;
+        }
+        let factor = 1.0 - dividend.value / cum_close;
+        for f in factors[..ex].iter_mut() {
+            *f *= factor;
+        }
+    }
+
+    scale(history, &factors)
+}
+
+// prices before a split are divided and volumes multiplied by after / before
+pub fn adjust_splits(history: Vec<HistoryEntry>, splits: &[Split]) -> Vec<HistoryEntry> {
+    let mut factors = vec![1.0; history.len()];
+    for split in splits {
+        if split.before <= 0 || split.after <= 0 {
+            continue;
+        }
+        let first_after = history.partition_point(|e| e.date < split.date);
+        let factor = split.before as f64 / split.after as f64;
+        for f in factors[..first_after].iter_mut() {
+            *f *= factor;
+        }
+    }
+
+    scale(history, &factors)
+        .into_iter()
+        .zip(&factors)
+        .map(|(mut entry, factor)| {
+            entry.volume = (entry.volume as f64 / factor).round() as i64;
+            entry
+        })
+        .collect()
+}
+
+fn scale(history: Vec<HistoryEntry>, factors: &[f64]) -> Vec<HistoryEntry> {
+    history
+        .into_iter()
+        .zip(factors)
+        .map(|(mut entry, factor)| {
+            entry.open *= factor;
+            entry.close *= factor;
+            entry.high *= factor;
+            entry.low *= factor;
+            entry
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(date: &str, close: f64, volume: i64) -> HistoryEntry {
+        HistoryEntry {
+            date: date.parse().unwrap(),
+            open: close,
+            close,
+            high: close,
+            low: close,
+            volume,
+            facevalue: 1,
+        }
+    }
+
+    #[test]
+    fn adjust_dividends_pass() {
+        let history = vec![
+            entry("2024-07-09", 110.0, 1),
+            entry("2024-07-10", 100.0, 1),
+            // registry close on a weekend, ex-dividend on monday
+            entry("2024-07-15", 90.0, 1),
+        ];
+        let dividends = [Dividend {
+            date: "2024-07-13".parse().unwrap(),
+            value: 10.0,
+            currency: "RUB".to_string(),
+        }];
+        let result = adjust_dividends(history, &dividends);
+        assert!((result[0].close - 99.0).abs() < 1e-9);
+        assert!((result[1].close - 90.0).abs() < 1e-9);
+        assert_eq!(result[2].close, 90.0);
+        assert_eq!(result[0].volume, 1);
+    }
+
+    #[test]
+    fn adjust_dividends_pass_t2_settlement() {
+        let history = vec![
+            entry("2022-07-07", 110.0, 1),
+            entry("2022-07-08", 100.0, 1),
+            // ex-dividend the trading day before the monday close date
+            entry("2022-07-11", 90.0, 1),
+            entry("2022-07-12", 91.0, 1),
+        ];
+        let dividends = [Dividend {
+            date: "2022-07-12".parse().unwrap(),
+            value: 10.0,
+            currency: "RUB".to_string(),
+        }];
+        let result = adjust_dividends(history, &dividends);
+        assert!((result[0].close - 99.0).abs() < 1e-9);
+        assert!((result[1].close - 90.0).abs() < 1e-9);
+        assert_eq!(result[2].close, 90.0);
+        assert_eq!(result[3].close, 91.0);
+    }
+
+    #[test]
+    fn adjust_dividends_pass_outside_history() {
+        let history = vec![entry("2024-07-10", 100.0, 1)];
+        let dividends = [
+            Dividend {
+                date: "2024-01-01".parse().unwrap(),
+                value: 10.0,
+                currency: "RUB".to_string(),
+            },
+            Dividend {
+                date: "2025-01-01".parse().unwrap(),
+                value: 10.0,
+                currency: "RUB".to_string(),
+            },
+        ];
+        assert_eq!(adjust_dividends(history.clone(), &dividends), history);
+    }
+
+    #[test]
+    fn adjust_splits_pass() {
+        let history = vec![
+            entry("2024-04-01", 15000.0, 10),
+            entry("2024-04-02", 150.0, 1000),
+        ];
+        let splits = [Split {
+            date: "2024-04-02".parse().unwrap(),
+            before: 1,
+            after: 100,
+        }];
+        let result = adjust_splits(history, &splits);
+        assert_eq!(
+            result,
+            vec![
+                entry("2024-04-01", 150.0, 1000),
+                entry("2024-04-02", 150.0, 1000)
+            ]
+        );
+    }
+
+    #[test]
+    fn adjust_splits_pass_reverse() {
+        let history = vec![
+            entry("2024-04-01", 1.0, 1000),
+            entry("2024-04-02", 10.0, 100),
+        ];
+        let splits = [Split {
+            date: "2024-04-02".parse().unwrap(),
+            before: 10,
+            after: 1,
+        }];
+        let result = adjust_splits(history, &splits);
+        assert_eq!(result[0], entry("2024-04-01", 10.0, 100));
+    }
+}
@@ -0,0 +1,145 @@
+use chrono::NaiveDate;
+use history_model::HistoryEntry;
+use serde::Deserialize;
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fill {
+    // gaps stay empty
+    #[default]
+    None,
+    // a gap takes the previous value of the same series
+    #[serde(alias = "forward", alias = "forward-fill")]
+    Ffill,
+    // only dates present in every series are kept
+    Drop,
+}
+
+// one date of several series, serialized flat as `[date, value, value, ...]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignedRow {
+    pub date: NaiveDate,
+    pub values: Vec<Option<f64>>,
+}
+
+impl AlignedRow {
+    pub fn is_complete(&self) -> bool {
+        self.values.iter().all(Option::is_some)
+    }
+}
+
+impl Serialize for AlignedRow {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.values.len() + 1))?;
+        seq.serialize_element(&self.date)?;
+        for value in &self.values {
+            seq.serialize_element(value)?;
+        }
+        seq.end()
+    }
+}
+
+// closes of every series on the union of their dates, in date order
+pub fn align(series: &[Vec<HistoryEntry>], fill: Fill) -> Vec<AlignedRow> {
+    let mut table: BTreeMap<NaiveDate, Vec<Option<f64>>> = BTreeMap::new();
+    for (column, history) in series.iter().enumerate() {
+        for entry in history {
+            table
+                .entry(entry.date)
+                .or_insert_with(|| vec![None; series.len()])[column] = Some(entry.close);
+        }
+    }
+
+    let rows = table
+        .into_iter()
+        .map(|(date, values)| AlignedRow { date, values });
+
+    match fill {
+        Fill::None => rows.collect(),
+        Fill::Drop => rows.filter(AlignedRow::is_complete).collect(),
+        Fill::Ffill => {
+            let mut last = vec![None; series.len()];
+            rows.map(|mut row| {
+                for (value, last) in row.values.iter_mut().zip(last.iter_mut()) {
+                    match value {
+                        Some(v) => *last = Some(*v),
+                        None => *value = *last,
+                    }
+                }
+                row
+            })
+            .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(date: &str, close: f64) -> HistoryEntry {
+        HistoryEntry {
+            date: date.parse().unwrap(),
+            open: close,
+            close,
+            high: close,
+            low: close,
+            volume: 0,
+            facevalue: 1,
+        }
+    }
+
+    fn row(date: &str, values: &[Option<f64>]) -> AlignedRow {
+        AlignedRow {
+            date: date.parse().unwrap(),
+            values: values.to_vec(),
+        }
+    }
+
+    fn series() -> Vec<Vec<HistoryEntry>> {
+        vec![
+            vec![entry("2024-01-01", 1.0), entry("2024-01-03", 3.0)],
+            vec![entry("2024-01-02", 20.0), entry("2024-01-03", 30.0)],
+        ]
+    }
+
+    #[test]
+    fn align_pass_none() {
+        assert_eq!(
+            align(&series(), Fill::None),
+            vec![
+                row("2024-01-01", &[Some(1.0), None]),
+                row("2024-01-02", &[None, Some(20.0)]),
+                row("2024-01-03", &[Some(3.0), Some(30.0)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn align_pass_ffill() {
+        assert_eq!(
+            align(&series(), Fill::Ffill),
+            vec![
+                row("2024-01-01", &[Some(1.0), None]),
+                row("2024-01-02", &[Some(1.0), Some(20.0)]),
+                row("2024-01-03", &[Some(3.0), Some(30.0)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn align_pass_drop() {
+        assert_eq!(
+            align(&series(), Fill::Drop),
+            vec![row("2024-01-03", &[Some(3.0), Some(30.0)])]
+        );
+    }
+
+    #[test]
+    fn aligned_row_pass_serialize_flat() {
+        let result = serde_json::to_string(&row("2024-01-01", &[Some(1.5), None])).unwrap();
+        assert_eq!(result, r#"["2024-01-01",1.5,null]"#);
+    }
+}
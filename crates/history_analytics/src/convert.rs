@@ -0,0 +1,135 @@
+use chrono::NaiveDate;
+use history_model::HistoryEntry;
+
+// daily exchange rates sorted by date, a missing day takes the last known rate
+// so weekends and holidays carry the previous fixing forward
+#[derive(Debug, Clone, Default)]
+pub struct RateTable {
+    rates: Vec<(NaiveDate, f64)>,
+}
+
+impl RateTable {
+    pub fn from_history(history: &[HistoryEntry]) -> Self {
+        let mut rates: Vec<(NaiveDate, f64)> = history
+            .iter()
+            .filter(|e| e.close > 0.0)
+            .map(|e| (e.date, e.close))
+            .collect();
+        rates.sort_by_key(|(date, _)| *date);
+        RateTable { rates }
+    }
+
+    pub fn rate_on(&self, date: NaiveDate) -> Option<f64> {
+        let idx = self.rates.partition_point(|(d, _)| *d <= date);
+        idx.checked_sub(1).map(|i| self.rates[i].1)
+    }
+}
+
+// converts prices quoted in the source currency into the target one through a common base,
+// `None` stands for the base currency itself. entries older than the first known rate are dropped.
+// a bond quote is a percent of its facevalue, so bonds come out as prices in money with facevalue 1
+pub fn convert(
+    history: Vec<HistoryEntry>,
+    source: Option<&RateTable>,
+    target: Option<&RateTable>,
+) -> Vec<HistoryEntry> {
+    let rate = |table: Option<&RateTable>, date| match table {
+        Some(table) => table.rate_on(date),
+        None => Some(1.0),
+    };
+
+    history
+        .into_iter()
+        .filter_map(|entry| {
+            let factor = rate(source, entry.date)? / rate(target, entry.date)?;
+            Some(HistoryEntry {
+                open: entry.in_money(entry.open) * factor,
+                close: entry.price() * factor,
+                high: entry.in_money(entry.high) * factor,
+                low: entry.in_money(entry.low) * factor,
+                facevalue: 1,
+                ..entry
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(date: &str, close: f64) -> HistoryEntry {
+        HistoryEntry {
+            date: date.parse().unwrap(),
+            open: close,
+            close,
+            high: close * 2.0,
+            low: close / 2.0,
+            volume: 7,
+            facevalue: 1,
+        }
+    }
+
+    fn date(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    #[test]
+    fn rate_on_pass_carry_forward() {
+        let table =
+            RateTable::from_history(&[entry("2024-01-05", 90.0), entry("2024-01-09", 91.0)]);
+        assert_eq!(table.rate_on(date("2024-01-04")), None);
+        assert_eq!(table.rate_on(date("2024-01-05")), Some(90.0));
+        assert_eq!(table.rate_on(date("2024-01-07")), Some(90.0));
+        assert_eq!(table.rate_on(date("2024-01-30")), Some(91.0));
+    }
+
+    #[test]
+    fn convert_pass_to_base() {
+        let usd = RateTable::from_history(&[entry("2024-01-01", 90.0)]);
+        let result = convert(vec![entry("2024-01-02", 2.0)], Some(&usd), None);
+        assert_eq!(
+            result,
+            vec![HistoryEntry {
+                date: date("2024-01-02"),
+                open: 180.0,
+                close: 180.0,
+                high: 360.0,
+                low: 90.0,
+                volume: 7,
+                facevalue: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn convert_pass_from_base() {
+        let usd = RateTable::from_history(&[entry("2024-01-01", 100.0)]);
+        let result = convert(vec![entry("2024-01-02", 250.0)], None, Some(&usd));
+        assert_eq!(result[0].close, 2.5);
+    }
+
+    #[test]
+    fn convert_pass_bond_price() {
+        let usd = RateTable::from_history(&[entry("2024-01-01", 100.0)]);
+        let mut bond = entry("2024-01-02", 98.0);
+        bond.facevalue = 1000;
+        let result = convert(vec![bond], None, Some(&usd));
+        assert!((result[0].close - 9.8).abs() < 1e-12);
+        assert!((result[0].high - 19.6).abs() < 1e-12);
+        assert_eq!(result[0].facevalue, 1);
+    }
+
+    #[test]
+    fn convert_pass_cross_rate_drop_unknown() {
+        let usd = RateTable::from_history(&[entry("2024-01-02", 100.0)]);
+        let eur = RateTable::from_history(&[entry("2024-01-01", 110.0)]);
+        let result = convert(
+            vec![entry("2024-01-01", 10.0), entry("2024-01-03", 10.0)],
+            Some(&eur),
+            Some(&usd),
+        );
+        assert_eq!(result.len(), 1);
+        assert!((result[0].close - 11.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,183 @@
+use chrono::NaiveDate;
+use history_model::HistoryEntry;
+use serde::Serialize;
+
+use crate::align::{self, Fill};
+use crate::stats::mean;
+
+// correlations and betas are None when a series has no variance or there are
+// less than two common returns
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Correlation {
+    pub from: Option<NaiveDate>,
+    pub till: Option<NaiveDate>,
+    pub observations: usize,
+    pub matrix: Vec<Vec<Option<f64>>>,
+    // against the benchmark series
+    pub beta: Vec<Option<f64>>,
+}
+
+// close to close returns on the dates common to every series, the window keeps
+// only the latest returns. benchmark is the index of the series betas are against
+pub fn correlation(
+    series: &[Vec<HistoryEntry>],
+    benchmark: usize,
+    window: Option<usize>,
+) -> Correlation {
+    let rows = align::align(series, Fill::Drop);
+    let mut dates = Vec::new();
+    let mut returns = vec![Vec::new(); series.len()];
+    for pair in rows.windows(2) {
+        let changes: Option<Vec<f64>> = pair[0]
+            .values
+            .iter()
+            .zip(&pair[1].values)
+            .map(|(previous, current)| match (previous, current) {
+                (Some(p), Some(c)) if *p > 0.0 && *c > 0.0 => Some(c / p - 1.0),
+                _ => None,
+            })
+            .collect();
+        if let Some(changes) = changes {
+            dates.push(pair[1].date);
+            for (column, change) in returns.iter_mut().zip(changes) {
+                column.push(change);
+            }
+        }
+    }
+
+    let skip = window.map_or(0, |window| dates.len().saturating_sub(window));
+    let dates = &dates[skip..];
+    let returns: Vec<&[f64]> = returns.iter().map(|r| &r[skip..]).collect();
+
+    let matrix = returns
+        .iter()
+        .map(|a| returns.iter().map(|b| pearson(a, b)).collect())
+        .collect();
+    let beta = returns
+        .iter()
+        .map(|a| beta(a, returns.get(benchmark)?))
+        .collect();
+
+    Correlation {
+        from: dates.first().copied(),
+        till: dates.last().copied(),
+        observations: dates.len(),
+        matrix,
+        beta,
+    }
+}
+
+fn covariance(a: &[f64], b: &[f64]) -> Option<f64> {
+    if a.len() < 2 {
+        return None;
+    }
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    let sum: f64 = a
+        .iter()
+        .zip(b)
+        .map(|(a, b)| (a - mean_a) * (b - mean_b))
+        .sum();
+    Some(sum / (a.len() - 1) as f64)
+}
+
+fn pearson(a: &[f64], b: &[f64]) -> Option<f64> {
+    let (var_a, var_b) = (covariance(a, a)?, covariance(b, b)?);
+    if var_a <= 0.0 || var_b <= 0.0 {
+        return None;
+    }
+    Some((covariance(a, b)? / (var_a * var_b).sqrt()).clamp(-1.0, 1.0))
+}
+
+fn beta(a: &[f64], benchmark: &[f64]) -> Option<f64> {
+    let variance = covariance(benchmark, benchmark)?;
+    if variance <= 0.0 {
+        return None;
+    }
+    Some(covariance(a, benchmark)? / variance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(closes: &[(&str, f64)]) -> Vec<HistoryEntry> {
+        closes
+            .iter()
+            .map(|(date, close)| HistoryEntry {
+                date: date.parse().unwrap(),
+                open: *close,
+                close: *close,
+                high: *close,
+                low: *close,
+                volume: 0,
+                facevalue: 1,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn correlation_pass() {
+        let benchmark = history(&[
+            ("2024-01-01", 100.0),
+            ("2024-01-02", 110.0),
+            ("2024-01-03", 99.0),
+            ("2024-01-04", 108.9),
+        ]);
+        // twice the benchmark returns
+        let leveraged = history(&[
+            ("2024-01-01", 100.0),
+            ("2024-01-02", 120.0),
+            ("2024-01-03", 96.0),
+            ("2024-01-04", 115.2),
+        ]);
+        let result = correlation(&[leveraged, benchmark], 1, None);
+
+        assert_eq!(result.observations, 3);
+        assert_eq!(result.from, Some("2024-01-02".parse().unwrap()));
+        assert!((result.matrix[0][1].unwrap() - 1.0).abs() < 1e-9);
+        assert!((result.matrix[1][0].unwrap() - 1.0).abs() < 1e-9);
+        assert!((result.beta[0].unwrap() - 2.0).abs() < 1e-9);
+        assert!((result.beta[1].unwrap() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn correlation_pass_window_and_common_dates() {
+        let a = history(&[
+            ("2024-01-01", 100.0),
+            ("2024-01-02", 110.0),
+            ("2024-01-03", 100.0),
+            ("2024-01-04", 110.0),
+        ]);
+        // no 2024-01-02, so the first common return is 2024-01-01 to 2024-01-03
+        let b = history(&[
+            ("2024-01-01", 100.0),
+            ("2024-01-03", 90.0),
+            ("2024-01-04", 99.0),
+        ]);
+        let result = correlation(&[a, b], 1, Some(1));
+
+        assert_eq!(result.observations, 1);
+        assert_eq!(result.till, Some("2024-01-04".parse().unwrap()));
+        assert_eq!(result.matrix[0][1], None);
+        assert_eq!(result.beta[0], None);
+    }
+
+    #[test]
+    fn correlation_pass_flat_series() {
+        let a = history(&[
+            ("2024-01-01", 100.0),
+            ("2024-01-02", 110.0),
+            ("2024-01-03", 100.0),
+        ]);
+        let flat = history(&[
+            ("2024-01-01", 1.0),
+            ("2024-01-02", 1.0),
+            ("2024-01-03", 1.0),
+        ]);
+        let result = correlation(&[a, flat], 1, None);
+
+        assert_eq!(result.matrix[0][1], None);
+        assert_eq!(result.matrix[1][1], None);
+        assert_eq!(result.beta, vec![None, None]);
+    }
+}
@@ -0,0 +1,311 @@
+use std::error::Error;
+use std::fmt;
+
+// the parser and eval recurse, user input is bounded before either runs
+const MAX_LENGTH: usize = 1000;
+const MAX_DEPTH: usize = 32;
+
+// arithmetic over named series like `moex:imoex / cbr:usd` or `(moex:a - moex:b) * 100`.
+// symbols start with a letter and may contain letters, digits, `_`, `.` and `:`,
+// a `-` is always the minus operator
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Symbol(String),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, Op, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Symbol(String),
+    Op(Op),
+    Open,
+    Close,
+}
+
+impl Expr {
+    pub fn parse(input: &str) -> Result<Expr, ExpressionError> {
+        if input.chars().count() > MAX_LENGTH {
+            return Err(ExpressionError::TooLong(MAX_LENGTH));
+        }
+        let tokens = tokenize(input)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            depth: 0,
+        };
+        let expr = parser.expr()?;
+        if parser.pos != parser.tokens.len() {
+            return Err(ExpressionError::Unexpected(parser.pos));
+        }
+        Ok(expr)
+    }
+
+    // distinct symbols in order of appearance
+    pub fn symbols(&self) -> Vec<&str> {
+        let mut out = Vec::new();
+        self.collect_symbols(&mut out);
+        out
+    }
+
+    fn collect_symbols<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match self {
+            Expr::Number(_) => {}
+            Expr::Symbol(name) => {
+                if !out.contains(&name.as_str()) {
+                    out.push(name);
+                }
+            }
+            Expr::Neg(inner) => inner.collect_symbols(out),
+            Expr::Binary(left, _, right) => {
+                left.collect_symbols(out);
+                right.collect_symbols(out);
+            }
+        }
+    }
+
+    // None when a symbol has no value or the result is not finite, e.g. division by zero
+    pub fn eval(&self, lookup: &impl Fn(&str) -> Option<f64>) -> Option<f64> {
+        let value = match self {
+            Expr::Number(value) => *value,
+            Expr::Symbol(name) => lookup(name)?,
+            Expr::Neg(inner) => -inner.eval(lookup)?,
+            Expr::Binary(left, op, right) => {
+                let (left, right) = (left.eval(lookup)?, right.eval(lookup)?);
+                match op {
+                    Op::Add => left + right,
+                    Op::Sub => left - right,
+                    Op::Mul => left * right,
+                    Op::Div => left / right,
+                }
+            }
+        };
+        value.is_finite().then_some(value)
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ExpressionError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        i += 1;
+        match c {
+            ' ' | '\t' => {}
+            '+' => tokens.push(Token::Op(Op::Add)),
+            '-' => tokens.push(Token::Op(Op::Sub)),
+            '*' => tokens.push(Token::Op(Op::Mul)),
+            '/' => tokens.push(Token::Op(Op::Div)),
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            c if c.is_ascii_digit() || c == '.' => {
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let number: String = chars[start..i].iter().collect();
+                let number = number
+                    .parse()
+                    .map_err(|_| ExpressionError::InvalidNumber(number))?;
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_alphabetic() => {
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '.' | ':'))
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Symbol(chars[start..i].iter().collect()));
+            }
+            c => return Err(ExpressionError::InvalidChar(c)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    // open parentheses and unary minuses around the current factor
+    depth: usize,
+}
+
+impl Parser {
+    fn next_op(&mut self, ops: &[Op]) -> Option<Op> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                self.pos += 1;
+                Some(*op)
+            }
+            _ => None,
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, ExpressionError> {
+        let mut left = self.term()?;
+        while let Some(op) = self.next_op(&[Op::Add, Op::Sub]) {
+            left = Expr::Binary(Box::new(left), op, Box::new(self.term()?));
+        }
+        Ok(left)
+    }
+
+    fn term(&mut self) -> Result<Expr, ExpressionError> {
+        let mut left = self.factor()?;
+        while let Some(op) = self.next_op(&[Op::Mul, Op::Div]) {
+            left = Expr::Binary(Box::new(left), op, Box::new(self.factor()?));
+        }
+        Ok(left)
+    }
+
+    fn factor(&mut self) -> Result<Expr, ExpressionError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(ExpressionError::TooDeep(MAX_DEPTH));
+        }
+        self.depth += 1;
+        let factor = self.nested_factor();
+        self.depth -= 1;
+        factor
+    }
+
+    fn nested_factor(&mut self) -> Result<Expr, ExpressionError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or(ExpressionError::UnexpectedEnd)?;
+        self.pos += 1;
+
+        match token {
+            Token::Number(value) => Ok(Expr::Number(value)),
+            Token::Symbol(name) => Ok(Expr::Symbol(name)),
+            Token::Op(Op::Sub) => Ok(Expr::Neg(Box::new(self.factor()?))),
+            Token::Open => {
+                let inner = self.expr()?;
+                if self.tokens.get(self.pos) != Some(&Token::Close) {
+                    return Err(ExpressionError::Unexpected(self.pos));
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            _ => Err(ExpressionError::Unexpected(self.pos - 1)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ExpressionError {
+    InvalidChar(char),
+    InvalidNumber(String),
+    Unexpected(usize),
+    UnexpectedEnd,
+    TooLong(usize),
+    TooDeep(usize),
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExpressionError::InvalidChar(c) => write!(f, "invalid character: {}", c),
+            ExpressionError::InvalidNumber(n) => write!(f, "invalid number: {}", n),
+            ExpressionError::Unexpected(pos) => write!(f, "unexpected token at {}", pos),
+            ExpressionError::UnexpectedEnd => write!(f, "unexpected end of expression"),
+            ExpressionError::TooLong(max) => write!(f, "expression longer than {} characters", max),
+            ExpressionError::TooDeep(max) => write!(f, "expression nested deeper than {}", max),
+        }
+    }
+}
+
+impl Error for ExpressionError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(input: &str) -> Option<f64> {
+        Expr::parse(input).unwrap().eval(&|name| match name {
+            "moex:imoex" => Some(3000.0),
+            "cbr:usd" => Some(100.0),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn parse_pass_precedence() {
+        assert_eq!(eval("1 + 2 * 3"), Some(7.0));
+        assert_eq!(eval("(1 + 2) * 3"), Some(9.0));
+        assert_eq!(eval("8 / 4 / 2"), Some(1.0));
+        assert_eq!(eval("10 - 2 - 3"), Some(5.0));
+        assert_eq!(eval("-2 * -(1 + 1)"), Some(4.0));
+    }
+
+    #[test]
+    fn parse_pass_symbols() {
+        assert_eq!(eval("moex:imoex / cbr:usd"), Some(30.0));
+        assert_eq!(eval("moex:imoex/cbr:usd-0.5"), Some(29.5));
+    }
+
+    #[test]
+    fn symbols_pass_distinct() {
+        let expr = Expr::parse("(moex:a - moex:b) / moex:a").unwrap();
+        assert_eq!(expr.symbols(), vec!["moex:a", "moex:b"]);
+    }
+
+    #[test]
+    fn eval_fail_missing_symbol() {
+        assert_eq!(eval("moex:unknown + 1"), None);
+    }
+
+    #[test]
+    fn eval_fail_division_by_zero() {
+        assert_eq!(eval("cbr:usd / (1 - 1)"), None);
+    }
+
+    #[test]
+    fn parse_fail() {
+        assert_eq!(
+            Expr::parse("moex:a $ 2"),
+            Err(ExpressionError::InvalidChar('$'))
+        );
+        assert_eq!(Expr::parse("(1 + 2"), Err(ExpressionError::Unexpected(4)));
+        assert_eq!(Expr::parse("1 +"), Err(ExpressionError::UnexpectedEnd));
+        assert_eq!(Expr::parse("1 2"), Err(ExpressionError::Unexpected(1)));
+        assert_eq!(
+            Expr::parse("1.2.3"),
+            Err(ExpressionError::InvalidNumber("1.2.3".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_fail_too_deep() {
+        let nested = format!("{}1{}", "(".repeat(100), ")".repeat(100));
+        assert_eq!(
+            Expr::parse(&nested),
+            Err(ExpressionError::TooDeep(MAX_DEPTH))
+        );
+        assert_eq!(
+            Expr::parse(&"-".repeat(100)),
+            Err(ExpressionError::TooDeep(MAX_DEPTH))
+        );
+        assert_eq!(
+            Expr::parse(&"1+".repeat(1000)),
+            Err(ExpressionError::TooLong(MAX_LENGTH))
+        );
+        assert_eq!(
+            eval(&format!("{}1{}", "(".repeat(10), ")".repeat(10))),
+            Some(1.0)
+        );
+    }
+}
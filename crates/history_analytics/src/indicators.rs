@@ -0,0 +1,168 @@
+use serde::Serialize;
+
+// all indicators return one value per input close, `None` until the window is filled
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Band {
+    pub middle: f64,
+    pub upper: f64,
+    pub lower: f64,
+}
+
+pub fn sma(closes: &[f64], window: usize) -> Vec<Option<f64>> {
+    let mut out = vec![None; closes.len()];
+    if window == 0 || closes.len() < window {
+        return out;
+    }
+
+    let mut sum: f64 = closes[..window].iter().sum();
+    out[window - 1] = Some(sum / window as f64);
+    for i in window..closes.len() {
+        sum += closes[i] - closes[i - window];
+        out[i] = Some(sum / window as f64);
+    }
+    out
+}
+
+// seeded with the sma of the first window
+pub fn ema(closes: &[f64], window: usize) -> Vec<Option<f64>> {
+    let mut out = vec![None; closes.len()];
+    if window == 0 || closes.len() < window {
+        return out;
+    }
+
+    let alpha = 2.0 / (window as f64 + 1.0);
+    let mut value = closes[..window].iter().sum::<f64>() / window as f64;
+    out[window - 1] = Some(value);
+    for i in window..closes.len() {
+        value = alpha * closes[i] + (1.0 - alpha) * value;
+        out[i] = Some(value);
+    }
+    out
+}
+
+// wilder's smoothing, the first value needs `window` price changes
+pub fn rsi(closes: &[f64], window: usize) -> Vec<Option<f64>> {
+    let mut out = vec![None; closes.len()];
+    if window == 0 || closes.len() <= window {
+        return out;
+    }
+
+    let change = |i: usize| closes[i] - closes[i - 1];
+    let mut avg_gain = (1..=window).map(|i| change(i).max(0.0)).sum::<f64>() / window as f64;
+    let mut avg_loss = (1..=window).map(|i| (-change(i)).max(0.0)).sum::<f64>() / window as f64;
+    out[window] = Some(rsi_value(avg_gain, avg_loss));
+
+    for (i, value) in out.iter_mut().enumerate().skip(window + 1) {
+        avg_gain = (avg_gain * (window as f64 - 1.0) + change(i).max(0.0)) / window as f64;
+        avg_loss = (avg_loss * (window as f64 - 1.0) + (-change(i)).max(0.0)) / window as f64;
+        *value = Some(rsi_value(avg_gain, avg_loss));
+    }
+    out
+}
+
+fn rsi_value(avg_gain: f64, avg_loss: f64) -> f64 {
+    if avg_loss == 0.0 {
+        return 100.0;
+    }
+    100.0 - 100.0 / (1.0 + avg_gain / avg_loss)
+}
+
+// sma middle line with bands `width` population standard deviations away
+pub fn bollinger(closes: &[f64], window: usize, width: f64) -> Vec<Option<Band>> {
+    sma(closes, window)
+        .into_iter()
+        .enumerate()
+        .map(|(i, middle)| {
+            let middle = middle?;
+            let variance = closes[i + 1 - window..=i]
+                .iter()
+                .map(|c| (c - middle).powi(2))
+                .sum::<f64>()
+                / window as f64;
+            let deviation = variance.sqrt() * width;
+            Some(Band {
+                middle,
+                upper: middle + deviation,
+                lower: middle - deviation,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(result: &[Option<f64>], expected: &[Option<f64>]) {
+        assert_eq!(result.len(), expected.len());
+        for (r, e) in result.iter().zip(expected) {
+            match (r, e) {
+                (Some(r), Some(e)) => assert!((r - e).abs() < 1e-9, "{} != {}", r, e),
+                _ => assert_eq!(r, e),
+            }
+        }
+    }
+
+    #[test]
+    fn sma_pass() {
+        let result = sma(&[1.0, 2.0, 3.0, 4.0], 2);
+        assert_close(&result, &[None, Some(1.5), Some(2.5), Some(3.5)]);
+    }
+
+    #[test]
+    fn sma_pass_short_series() {
+        assert_eq!(sma(&[1.0], 2), vec![None]);
+        assert_eq!(sma(&[1.0], 0), vec![None]);
+    }
+
+    #[test]
+    fn ema_pass() {
+        // alpha = 0.5 for window 3
+        let result = ema(&[1.0, 2.0, 3.0, 5.0, 1.0], 3);
+        assert_close(&result, &[None, None, Some(2.0), Some(3.5), Some(2.25)]);
+    }
+
+    #[test]
+    fn rsi_pass_only_gains() {
+        let result = rsi(&[1.0, 2.0, 3.0, 4.0], 2);
+        assert_close(&result, &[None, None, Some(100.0), Some(100.0)]);
+    }
+
+    #[test]
+    fn rsi_pass_wilder_smoothing() {
+        // changes: +2, -1, +1
+        let result = rsi(&[10.0, 12.0, 11.0, 12.0], 2);
+        // gains 1.0 / losses 0.5, then (1 + 1) / 2 = 1.0 / 0.25
+        assert_close(
+            &result,
+            &[
+                None,
+                None,
+                Some(100.0 - 100.0 / 3.0),
+                Some(100.0 - 100.0 / 5.0),
+            ],
+        );
+    }
+
+    #[test]
+    fn bollinger_pass() {
+        let result = bollinger(&[1.0, 3.0, 3.0], 2, 2.0);
+        assert_eq!(
+            result,
+            vec![
+                None,
+                Some(Band {
+                    middle: 2.0,
+                    upper: 4.0,
+                    lower: 0.0
+                }),
+                Some(Band {
+                    middle: 3.0,
+                    upper: 3.0,
+                    lower: 3.0
+                }),
+            ]
+        );
+    }
+}
@@ -0,0 +1,9 @@
+pub mod adjust;
+pub mod align;
+pub mod convert;
+pub mod correlation;
+pub mod expression;
+pub mod indicators;
+pub mod portfolio;
+pub mod resample;
+pub mod stats;
@@ -0,0 +1,225 @@
+use chrono::NaiveDate;
+use history_model::HistoryEntry;
+use serde::Serialize;
+
+use crate::align::{self, Fill};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub quantity: f64,
+    // held from this date on, the whole history otherwise
+    pub purchase_date: Option<NaiveDate>,
+    // per unit, in the currency of the prices
+    pub purchase_price: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValuePoint {
+    pub date: NaiveDate,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PositionValue {
+    pub price: Option<f64>,
+    pub value: f64,
+    pub weight: f64,
+    pub cost: Option<f64>,
+    pub pnl: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Valuation {
+    pub series: Vec<ValuePoint>,
+    pub value: f64,
+    // totals over the positions with a known cost
+    pub cost: Option<f64>,
+    pub pnl: Option<f64>,
+    pub positions: Vec<PositionValue>,
+}
+
+// values positions given one price history per position, prices are in money
+// (see HistoryEntry::price) and all in the same currency. the daily series
+// forward fills prices and starts once every held position has a price
+pub fn value(positions: &[Position], prices: &[Vec<HistoryEntry>]) -> Valuation {
+    let priced: Vec<Vec<HistoryEntry>> = prices
+        .iter()
+        .map(|history| {
+            history
+                .iter()
+                .map(|entry| HistoryEntry {
+                    open: entry.in_money(entry.open),
+                    close: entry.price(),
+                    facevalue: 1,
+                    ..entry.clone()
+                })
+                .collect()
+        })
+        .collect();
+
+    let series: Vec<ValuePoint> = align::align(&priced, Fill::Ffill)
+        .into_iter()
+        .filter_map(|row| {
+            let mut value = 0.0;
+            for (position, price) in positions.iter().zip(&row.values) {
+                if position.purchase_date.is_some_and(|date| row.date < date) {
+                    continue;
+                }
+                value += position.quantity * (*price)?;
+            }
+            Some(ValuePoint {
+                date: row.date,
+                value,
+            })
+        })
+        .collect();
+
+    let mut values: Vec<PositionValue> = positions
+        .iter()
+        .zip(&priced)
+        .map(|(position, history)| {
+            let price = history.last().map(|e| e.close);
+            let value = position.quantity * price.unwrap_or_default();
+            let cost = position
+                .purchase_price
+                .or_else(|| {
+                    let date = position.purchase_date?;
+                    history.iter().find(|e| e.date >= date).map(|e| e.close)
+                })
+                .map(|price| position.quantity * price);
+            PositionValue {
+                price,
+                value,
+                weight: 0.0,
+                cost,
+                pnl: cost.map(|cost| value - cost),
+            }
+        })
+        .collect();
+
+    let total: f64 = values.iter().map(|v| v.value).sum();
+    if total != 0.0 {
+        for v in values.iter_mut() {
+            v.weight = v.value / total;
+        }
+    }
+
+    let known: Vec<&PositionValue> = values.iter().filter(|v| v.cost.is_some()).collect();
+    let cost = (!known.is_empty()).then(|| known.iter().filter_map(|v| v.cost).sum::<f64>());
+    let pnl = (!known.is_empty()).then(|| known.iter().filter_map(|v| v.pnl).sum::<f64>());
+
+    Valuation {
+        series,
+        value: total,
+        cost,
+        pnl,
+        positions: values,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(date: &str, close: f64, facevalue: i64) -> HistoryEntry {
+        HistoryEntry {
+            date: date.parse().unwrap(),
+            open: close,
+            close,
+            high: close,
+            low: close,
+            volume: 0,
+            facevalue,
+        }
+    }
+
+    fn date(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    fn position(quantity: f64) -> Position {
+        Position {
+            quantity,
+            purchase_date: None,
+            purchase_price: None,
+        }
+    }
+
+    #[test]
+    fn value_pass_stock_and_bond() {
+        let prices = vec![
+            vec![entry("2024-01-01", 100.0, 1), entry("2024-01-02", 110.0, 1)],
+            // 98% of 1000
+            vec![entry("2024-01-02", 98.0, 1000)],
+        ];
+        let result = value(&[position(10.0), position(2.0)], &prices);
+
+        assert_eq!(
+            result.series,
+            vec![ValuePoint {
+                date: date("2024-01-02"),
+                value: 1100.0 + 1960.0
+            }]
+        );
+        assert_eq!(result.value, 3060.0);
+        assert_eq!(result.positions[1].price, Some(980.0));
+        assert!((result.positions[0].weight - 1100.0 / 3060.0).abs() < 1e-12);
+        assert_eq!(result.cost, None);
+        assert_eq!(result.pnl, None);
+    }
+
+    #[test]
+    fn value_pass_purchase_date() {
+        let prices = vec![
+            vec![entry("2024-01-01", 100.0, 1), entry("2024-01-03", 120.0, 1)],
+            vec![entry("2024-01-01", 10.0, 1), entry("2024-01-02", 12.0, 1)],
+        ];
+        let positions = vec![
+            Position {
+                quantity: 1.0,
+                purchase_date: Some(date("2024-01-02")),
+                purchase_price: None,
+            },
+            Position {
+                quantity: 1.0,
+                purchase_date: None,
+                purchase_price: Some(5.0),
+            },
+        ];
+        let result = value(&positions, &prices);
+
+        // the first position is not held on the first day, then forward filled
+        assert_eq!(
+            result.series,
+            vec![
+                ValuePoint {
+                    date: date("2024-01-01"),
+                    value: 10.0
+                },
+                ValuePoint {
+                    date: date("2024-01-02"),
+                    value: 112.0
+                },
+                ValuePoint {
+                    date: date("2024-01-03"),
+                    value: 132.0
+                },
+            ]
+        );
+        // cost of the first position is the first price on or after the purchase date
+        assert_eq!(result.positions[0].cost, Some(120.0));
+        assert_eq!(result.positions[0].pnl, Some(0.0));
+        assert_eq!(result.positions[1].pnl, Some(7.0));
+        assert_eq!(result.cost, Some(125.0));
+        assert_eq!(result.pnl, Some(7.0));
+    }
+
+    #[test]
+    fn value_pass_no_prices() {
+        let result = value(&[position(1.0)], &[vec![]]);
+        assert_eq!(result.series, vec![]);
+        assert_eq!(result.value, 0.0);
+        assert_eq!(result.positions[0].price, None);
+        assert_eq!(result.positions[0].weight, 0.0);
+    }
+}
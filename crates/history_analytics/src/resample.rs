@@ -0,0 +1,134 @@
+use chrono::{Datelike, NaiveDate};
+use history_model::HistoryEntry;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl Period {
+    fn bucket(&self, date: NaiveDate) -> (i32, u32) {
+        match self {
+            Period::Week => (date.iso_week().year(), date.iso_week().week()),
+            Period::Month => (date.year(), date.month()),
+            Period::Quarter => (date.year(), date.month0() / 3),
+            Period::Year => (date.year(), 0),
+        }
+    }
+}
+
+// aggregates daily bars into one bar per period dated by its last trading day:
+// first open, last close, max high, min low, summed volume and the latest facevalue.
+// expects history sorted by date as every provider returns it
+pub fn resample(history: Vec<HistoryEntry>, period: Period) -> Vec<HistoryEntry> {
+    let mut out: Vec<HistoryEntry> = Vec::new();
+    let mut current_bucket = None;
+
+    for entry in history {
+        let bucket = period.bucket(entry.date);
+        match out.last_mut() {
+            Some(bar) if current_bucket == Some(bucket) => {
+                bar.date = entry.date;
+                bar.close = entry.close;
+                bar.high = bar.high.max(entry.high);
+                bar.low = bar.low.min(entry.low);
+                bar.volume += entry.volume;
+                bar.facevalue = entry.facevalue;
+            }
+            // the first bar of a period keeps its open
+            _ => {
+                current_bucket = Some(bucket);
+                out.push(entry);
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(date: &str, open: f64, close: f64, high: f64, low: f64, volume: i64) -> HistoryEntry {
+        HistoryEntry {
+            date: date.parse().unwrap(),
+            open,
+            close,
+            high,
+            low,
+            volume,
+            facevalue: 1,
+        }
+    }
+
+    #[test]
+    fn resample_pass_empty() {
+        assert_eq!(resample(vec![], Period::Month), vec![]);
+    }
+
+    #[test]
+    fn resample_pass_week() {
+        let history = vec![
+            // friday and the following monday belong to different iso weeks
+            entry("2024-01-05", 9.5, 10.0, 11.0, 9.0, 100),
+            entry("2024-01-08", 11.5, 12.0, 13.0, 11.0, 10),
+            entry("2024-01-09", 12.0, 11.0, 14.0, 8.0, 20),
+        ];
+        let result = resample(history, Period::Week);
+        assert_eq!(
+            result,
+            vec![
+                entry("2024-01-05", 9.5, 10.0, 11.0, 9.0, 100),
+                entry("2024-01-09", 11.5, 11.0, 14.0, 8.0, 30),
+            ]
+        );
+    }
+
+    #[test]
+    fn resample_pass_week_year_boundary() {
+        // 2024-12-31 is in iso week 1 of 2025
+        let history = vec![
+            entry("2024-12-31", 1.0, 1.0, 1.0, 1.0, 1),
+            entry("2025-01-02", 2.0, 2.0, 2.0, 2.0, 1),
+        ];
+        let result = resample(history, Period::Week);
+        assert_eq!(result, vec![entry("2025-01-02", 1.0, 2.0, 2.0, 1.0, 2)]);
+    }
+
+    #[test]
+    fn resample_pass_quarter() {
+        let history = vec![
+            entry("2024-01-10", 1.5, 1.0, 5.0, 1.0, 1),
+            entry("2024-03-29", 1.0, 2.0, 2.0, 0.5, 1),
+            entry("2024-04-01", 3.0, 3.0, 3.0, 3.0, 1),
+        ];
+        let result = resample(history, Period::Quarter);
+        assert_eq!(
+            result,
+            vec![
+                entry("2024-03-29", 1.5, 2.0, 5.0, 0.5, 2),
+                entry("2024-04-01", 3.0, 3.0, 3.0, 3.0, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn resample_pass_year_facevalue() {
+        let mut amortized = entry("2024-06-03", 99.0, 99.0, 99.0, 99.0, 1);
+        amortized.facevalue = 500;
+        let mut first = entry("2024-01-10", 100.0, 100.0, 100.0, 100.0, 1);
+        first.facevalue = 1000;
+
+        let result = resample(vec![first, amortized.clone()], Period::Year);
+        amortized.open = 100.0;
+        amortized.high = 100.0;
+        amortized.volume = 2;
+        assert_eq!(result, vec![amortized]);
+    }
+}
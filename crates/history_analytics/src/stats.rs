@@ -0,0 +1,185 @@
+use chrono::NaiveDate;
+use history_model::HistoryEntry;
+use serde::Serialize;
+
+pub const TRADING_DAYS: f64 = 252.0;
+const DAYS_IN_YEAR: f64 = 365.25;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DayReturn {
+    pub date: NaiveDate,
+    #[serde(rename = "return")]
+    pub value: f64,
+}
+
+// returns and drawdowns are fractions, 0.1 means 10%
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stats {
+    pub from: NaiveDate,
+    pub till: NaiveDate,
+    pub observations: usize,
+    pub total_return: f64,
+    pub annualized_return: f64,
+    pub volatility: f64,
+    pub max_drawdown: f64,
+    pub sharpe_ratio: Option<f64>,
+    pub best_day: DayReturn,
+    pub worst_day: DayReturn,
+}
+
+// close to close returns, entries with non-positive closes are skipped
+pub fn daily_returns(history: &[HistoryEntry]) -> Vec<DayReturn> {
+    let closes: Vec<&HistoryEntry> = history.iter().filter(|e| e.close > 0.0).collect();
+    closes
+        .windows(2)
+        .map(|pair| DayReturn {
+            date: pair[1].date,
+            value: pair[1].close / pair[0].close - 1.0,
+        })
+        .collect()
+}
+
+pub fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+// sample standard deviation, 0 for less than two values
+pub fn std_dev(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(values);
+    let variance =
+        values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+    variance.sqrt()
+}
+
+// largest peak to trough decline as a positive fraction
+pub fn max_drawdown(closes: &[f64]) -> f64 {
+    let mut peak = f64::MIN;
+    let mut drawdown: f64 = 0.0;
+    for close in closes {
+        peak = peak.max(*close);
+        if peak > 0.0 {
+            drawdown = drawdown.max((peak - close) / peak);
+        }
+    }
+    drawdown
+}
+
+// None when there are less than two usable closes.
+// volatility is annualized over trading days, the sharpe ratio uses an annual risk free rate
+pub fn stats(history: &[HistoryEntry], risk_free: f64) -> Option<Stats> {
+    let usable: Vec<&HistoryEntry> = history.iter().filter(|e| e.close > 0.0).collect();
+    let (first, last) = (usable.first()?, usable.last()?);
+    let returns = daily_returns(history);
+    if returns.is_empty() {
+        return None;
+    }
+
+    let total_return = last.close / first.close - 1.0;
+    let days = (last.date - first.date).num_days() as f64;
+    let annualized_return = if days > 0.0 {
+        (1.0 + total_return).powf(DAYS_IN_YEAR / days) - 1.0
+    } else {
+        total_return
+    };
+
+    let values: Vec<f64> = returns.iter().map(|r| r.value).collect();
+    let volatility = std_dev(&values) * TRADING_DAYS.sqrt();
+    let sharpe_ratio = (volatility > 0.0).then(|| (annualized_return - risk_free) / volatility);
+
+    let closes: Vec<f64> = usable.iter().map(|e| e.close).collect();
+    let best_day = returns.iter().max_by(|a, b| a.value.total_cmp(&b.value))?;
+    let worst_day = returns.iter().min_by(|a, b| a.value.total_cmp(&b.value))?;
+
+    Some(Stats {
+        from: first.date,
+        till: last.date,
+        observations: usable.len(),
+        total_return,
+        annualized_return,
+        volatility,
+        max_drawdown: max_drawdown(&closes),
+        sharpe_ratio,
+        best_day: best_day.clone(),
+        worst_day: worst_day.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(date: &str, close: f64) -> HistoryEntry {
+        HistoryEntry {
+            date: date.parse().unwrap(),
+            open: close,
+            close,
+            high: close,
+            low: close,
+            volume: 0,
+            facevalue: 1,
+        }
+    }
+
+    #[test]
+    fn daily_returns_pass_skip_zero() {
+        let history = vec![
+            entry("2024-01-01", 100.0),
+            entry("2024-01-02", 0.0),
+            entry("2024-01-03", 110.0),
+        ];
+        let result = daily_returns(&history);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].date, "2024-01-03".parse::<NaiveDate>().unwrap());
+        assert!((result[0].value - 0.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn std_dev_pass() {
+        assert_eq!(std_dev(&[1.0]), 0.0);
+        assert!((std_dev(&[1.0, 3.0]) - 2.0_f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn max_drawdown_pass() {
+        assert_eq!(max_drawdown(&[100.0, 120.0, 90.0, 130.0, 117.0]), 0.25);
+        assert_eq!(max_drawdown(&[1.0, 2.0, 3.0]), 0.0);
+    }
+
+    #[test]
+    fn stats_fail_single_entry() {
+        assert_eq!(stats(&[entry("2024-01-01", 1.0)], 0.0), None);
+    }
+
+    #[test]
+    fn stats_pass() {
+        let history = vec![
+            entry("2023-01-01", 100.0),
+            entry("2023-07-02", 80.0),
+            entry("2024-01-01", 120.0),
+        ];
+        let result = stats(&history, 0.0).unwrap();
+        assert_eq!(result.observations, 3);
+        assert!((result.total_return - 0.2).abs() < 1e-12);
+        // exactly one calendar year
+        assert!((result.annualized_return - 0.2).abs() < 1e-3);
+        assert_eq!(result.max_drawdown, 0.2);
+        assert_eq!(
+            result.best_day.date,
+            "2024-01-01".parse::<NaiveDate>().unwrap()
+        );
+        assert!((result.best_day.value - 0.5).abs() < 1e-12);
+        assert!((result.worst_day.value + 0.2).abs() < 1e-12);
+        assert!(result.sharpe_ratio.unwrap() > 0.0);
+    }
+
+    #[test]
+    fn stats_pass_flat_no_sharpe() {
+        let history = vec![entry("2024-01-01", 1.0), entry("2024-01-02", 1.0)];
+        let result = stats(&history, 0.0).unwrap();
+        assert_eq!(result.volatility, 0.0);
+        assert_eq!(result.sharpe_ratio, None);
+    }
+}
@@ -1,12 +1,45 @@
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct HistoryEntry {
     pub date: NaiveDate,
+    pub open: f64,
     pub close: f64,
     pub high: f64,
     pub low: f64,
     pub volume: i64,
     pub facevalue: i64,
 }
+
+impl HistoryEntry {
+    // close in money, bonds are quoted in percent of their facevalue
+    pub fn price(&self) -> f64 {
+        self.in_money(self.close)
+    }
+
+    // any quote of this entry (open, high, low) in money
+    pub fn in_money(&self, quote: f64) -> f64 {
+        if self.facevalue > 1 {
+            return quote * self.facevalue as f64 / 100.0;
+        }
+        quote
+    }
+}
+
+// cash dividend per share, `date` is the registry close date
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dividend {
+    pub date: NaiveDate,
+    pub value: f64,
+    // iso code as iss reports it, e.g. RUB or USD
+    pub currency: String,
+}
+
+// `before` shares became `after` shares starting from `date`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Split {
+    pub date: NaiveDate,
+    pub before: i64,
+    pub after: i64,
+}
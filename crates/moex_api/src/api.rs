@@ -1,13 +1,20 @@
 use chrono::NaiveDate;
-use history_model::HistoryEntry;
-use log::debug;
+use futures::{Stream, TryStreamExt, stream};
+use history_model::{Dividend, HistoryEntry, Split};
 use redis::AsyncCommands;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tracing::{Instrument, Span, debug, info_span, instrument};
+use upstream_client::{UpstreamClient, UpstreamConfig, UpstreamStatus};
 
 const DEFAULT_PAGE_SIZE: i64 = 100;
 const MOEX_BASE_API_URL: &str = "https://iss.moex.com";
+const ACTIONS_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
 
 #[derive(Debug, Serialize, Deserialize)]
 struct MoexSecurityParameters {
@@ -64,49 +71,309 @@ struct Marketdata {
     data: Vec<Vec<serde_json::Value>>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MoexDividendsJSON {
+    dividends: Table,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MoexSplitsJSON {
+    splits: Table,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Table {
+    columns: Vec<String>,
+    data: Vec<Vec<serde_json::Value>>,
+}
+
+fn redis_span(command: &'static str) -> Span {
+    info_span!("redis", db.system = "redis", db.operation = command)
+}
+
+// same operation names as the cache debug logs
+fn record_cache(operation: &'static str, hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    metrics::counter!("cache_requests_total", "operation" => operation, "result" => result)
+        .increment(1);
+}
+
 #[derive(Clone)]
 pub struct MoexAPI {
-    base_url: &'static str,
-    client: reqwest::Client,
+    base_url: String,
+    client: UpstreamClient,
     redis_client: redis::Client,
+    // reconnecting connection for readiness pings, opened by the first one
+    ping_con: Arc<OnceCell<redis::aio::ConnectionManager>>,
+    // corporate actions, splits and dividends
+    actions_ttl: Duration,
 }
 
 impl MoexAPI {
     pub fn new(redis_client: redis::Client) -> Self {
-        return MoexAPI {
-            base_url: MOEX_BASE_API_URL,
-            client: reqwest::Client::new(),
+        Self::with_config(redis_client, UpstreamConfig::default())
+    }
+
+    pub fn with_config(redis_client: redis::Client, upstream: UpstreamConfig) -> Self {
+        MoexAPI {
+            base_url: MOEX_BASE_API_URL.to_string(),
+            client: UpstreamClient::with_config("iss", upstream),
             redis_client,
-        };
+            ping_con: Arc::default(),
+            actions_ttl: ACTIONS_CACHE_TTL,
+        }
     }
 
+    // e.g. a mirror or a stub server
+    pub fn with_base_url(self, base_url: impl Into<String>) -> Self {
+        MoexAPI {
+            base_url: base_url.into(),
+            ..self
+        }
+    }
+
+    pub fn with_actions_ttl(self, actions_ttl: Duration) -> Self {
+        MoexAPI {
+            actions_ttl,
+            ..self
+        }
+    }
+
+    pub fn upstream_status(&self) -> UpstreamStatus {
+        self.client.status()
+    }
+
+    pub fn reconfigure(&self, upstream: UpstreamConfig) {
+        self.client.reconfigure(upstream);
+    }
+
+    // cache round trip for readiness checks
+    pub async fn ping_cache(&self) -> Result<(), Box<dyn Error>> {
+        let mut redis_con = self
+            .ping_con
+            .get_or_try_init(|| self.redis_client.get_connection_manager())
+            .await?
+            .clone();
+        redis::cmd("PING")
+            .query_async::<()>(&mut redis_con)
+            .instrument(redis_span("PING"))
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip(self), fields(exchange = "moex"))]
     pub async fn get_ticker(&self, ticker: &str) -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
+        self.stream_ticker(ticker).try_collect().await
+    }
+
+    // yields entries page by page as they arrive from ISS, the current price goes last
+    pub fn stream_ticker(
+        &self,
+        ticker: &str,
+    ) -> impl Stream<Item = Result<HistoryEntry, Box<dyn Error>>> + 'static {
+        let api = self.clone();
+        let ticker = ticker.to_string();
+
+        stream::try_unfold(None, move |cursor: Option<PageCursor>| {
+            let api = api.clone();
+            let ticker = ticker.clone();
+            async move {
+                let mut cursor = match cursor {
+                    Some(cursor) => cursor,
+                    None => {
+                        let mut redis_con =
+                            api.redis_client.get_multiplexed_async_connection().await?;
+                        let params = api.get_security_parameters(&ticker, &mut redis_con).await?;
+                        PageCursor {
+                            redis_con,
+                            params,
+                            offset: 0,
+                            total: DEFAULT_PAGE_SIZE,
+                            facevalue: 1,
+                            finished: false,
+                        }
+                    }
+                };
+
+                if cursor.finished {
+                    return Ok::<_, Box<dyn Error>>(None);
+                }
+
+                if cursor.offset < cursor.total {
+                    let entry_history = api
+                        .get_security_history_offset(
+                            &ticker,
+                            &cursor.params,
+                            cursor.offset,
+                            &mut cursor.redis_con,
+                        )
+                        .await?;
+                    cursor.total = entry_history.meta.total;
+                    cursor.offset += entry_history.meta.page_size;
+                    if let Some(last) = entry_history.history.last() {
+                        cursor.facevalue = last.facevalue;
+                    }
+                    return Ok(Some((entry_history.history, Some(cursor))));
+                }
+
+                cursor.finished = true;
+                let mut page = Vec::new();
+                if let Ok(mut current_price) = api
+                    .get_security_current_price(&ticker, &cursor.params)
+                    .await
+                {
+                    current_price.facevalue = cursor.facevalue;
+                    page.push(current_price);
+                }
+                Ok(Some((page, Some(cursor))))
+            }
+        })
+        .map_ok(|page| stream::iter(page.into_iter().map(Ok)))
+        .try_flatten()
+    }
+
+    #[instrument(skip(self), fields(exchange = "moex"))]
+    pub async fn get_dividends(&self, ticker: &str) -> Result<Vec<Dividend>, Box<dyn Error>> {
+        let url = format!(
+            "{}/iss/securities/{}/dividends.json?iss.meta=off&dividends.columns=registryclosedate,value,currencyid",
+            self.base_url, ticker
+        );
+
+        debug!("get_dividends | url: {}", url);
+
         let mut redis_con = self.redis_client.get_multiplexed_async_connection().await?;
+        if let Some(cached) = self
+            .cached_actions("get_dividends", &url, &mut redis_con)
+            .await?
+        {
+            return Ok(cached);
+        }
+
+        let json = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .json::<MoexDividendsJSON>()
+            .await?;
+
+        let mut dividends = Vec::new();
+        for entry in json.dividends.data {
+            let date =
+                NaiveDate::parse_from_str(entry[0].as_str().unwrap_or_default(), "%Y-%m-%d")?;
+            let value = entry[1].as_f64().unwrap_or_default();
+            let currency = entry
+                .get(2)
+                .and_then(|currency| currency.as_str())
+                .unwrap_or("RUB")
+                .to_string();
+            if value > 0.0 {
+                dividends.push(Dividend {
+                    date,
+                    value,
+                    currency,
+                });
+            }
+        }
+        dividends.sort_by_key(|d| d.date);
 
-        let params = self
-            .get_security_parameters(&ticker, &mut redis_con)
+        self.cache_actions("get_dividends", &url, &dividends, &mut redis_con)
             .await?;
-        let mut total = DEFAULT_PAGE_SIZE;
-        let mut offset: i64 = 0;
-        let mut history = Vec::new();
+        Ok(dividends)
+    }
 
-        while offset < total {
-            let entry_history = self
-                .get_security_history_offset(&ticker, &params, offset, &mut redis_con)
-                .await?;
-            total = entry_history.meta.total;
-            offset += entry_history.meta.page_size;
-            history.extend(entry_history.history);
+    // split events are rare but can be announced at any time, so they are cached for a day only
+    #[instrument(skip(self), fields(exchange = "moex"))]
+    pub async fn get_splits(&self, ticker: &str) -> Result<Vec<Split>, Box<dyn Error>> {
+        let url = format!(
+            "{}/iss/statistics/engines/stock/splits/{}.json?iss.meta=off&splits.columns=tradedate,before,after",
+            self.base_url, ticker
+        );
+
+        debug!("get_splits | url: {}", url);
+
+        let mut redis_con = self.redis_client.get_multiplexed_async_connection().await?;
+        if let Some(cached) = self
+            .cached_actions("get_splits", &url, &mut redis_con)
+            .await?
+        {
+            return Ok(cached);
         }
 
-        if let Ok(mut current_price) = self.get_security_current_price(&ticker, &params).await {
-            current_price.facevalue = history.last().unwrap().facevalue;
-            history.push(current_price);
+        let json = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .json::<MoexSplitsJSON>()
+            .await?;
+
+        let mut splits = Vec::new();
+        for entry in json.splits.data {
+            let date =
+                NaiveDate::parse_from_str(entry[0].as_str().unwrap_or_default(), "%Y-%m-%d")?;
+            let before = entry[1].as_i64().unwrap_or_default();
+            let after = entry[2].as_i64().unwrap_or_default();
+            if before > 0 && after > 0 {
+                splits.push(Split {
+                    date,
+                    before,
+                    after,
+                });
+            }
         }
+        splits.sort_by_key(|s| s.date);
 
-        Ok(history)
+        self.cache_actions("get_splits", &url, &splits, &mut redis_con)
+            .await?;
+        Ok(splits)
     }
 
+    async fn cached_actions<T: DeserializeOwned>(
+        &self,
+        operation: &'static str,
+        url: &str,
+        redis_con: &mut redis::aio::MultiplexedConnection,
+    ) -> Result<Option<Vec<T>>, Box<dyn Error>> {
+        let cached: Option<String> = redis_con.get(url).instrument(redis_span("GET")).await?;
+        let Some(cached) = cached else {
+            debug!(
+                cache_status = "miss",
+                "{} | cache miss | url: {}", operation, url
+            );
+            record_cache(operation, false);
+            return Ok(None);
+        };
+
+        debug!(
+            cache_status = "hit",
+            "{} | cache hit | key: {}", operation, url
+        );
+        record_cache(operation, true);
+        Ok(Some(serde_json::from_str(&cached)?))
+    }
+
+    // corporate actions can be announced at any time, so they expire after actions_ttl
+    async fn cache_actions<T: Serialize>(
+        &self,
+        operation: &'static str,
+        url: &str,
+        actions: &[T],
+        redis_con: &mut redis::aio::MultiplexedConnection,
+    ) -> Result<(), Box<dyn Error>> {
+        debug!("{} | saving to cache", operation);
+        let serialized = serde_json::to_string(actions)?;
+        let _: () = redis_con
+            .set_ex(url, &serialized, self.actions_ttl.as_secs())
+            .instrument(redis_span("SETEX"))
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip(self, redis_con))]
     async fn get_security_parameters(
         &self,
         ticker: &str,
@@ -119,14 +386,27 @@ impl MoexAPI {
 
         debug!("get_security_parameters | url: {}", url);
 
-        if redis_con.exists(&url).await? {
-            debug!("get_security_parameters | cache hit | key: {}", url);
-            let cached_params_str: String = redis_con.get(&url).await?;
+        if redis_con
+            .exists(&url)
+            .instrument(redis_span("EXISTS"))
+            .await?
+        {
+            debug!(
+                cache_status = "hit",
+                "get_security_parameters | cache hit | key: {}", url
+            );
+            record_cache("get_security_parameters", true);
+            let cached_params_str: String =
+                redis_con.get(&url).instrument(redis_span("GET")).await?;
             let cached_param: MoexSecurityParameters = serde_json::from_str(&cached_params_str)?;
             return Ok(cached_param);
         }
 
-        debug!("get_security_parameters | cache miss | url: {}", url);
+        debug!(
+            cache_status = "miss",
+            "get_security_parameters | cache miss | url: {}", url
+        );
+        record_cache("get_security_parameters", false);
 
         let moex_json = self
             .client
@@ -146,7 +426,10 @@ impl MoexAPI {
 
                 debug!("get_security_parameters | saving to cache");
                 let serialized = serde_json::to_string(&params)?;
-                let _: () = redis_con.set(&url, &serialized).await?;
+                let _: () = redis_con
+                    .set(&url, &serialized)
+                    .instrument(redis_span("SET"))
+                    .await?;
 
                 return Ok(params);
             }
@@ -155,21 +438,22 @@ impl MoexAPI {
         Err(Box::new(CustomError::NotFound))
     }
 
+    #[instrument(skip(self, params))]
     async fn get_security_current_price(
         &self,
         ticker: &str,
         params: &MoexSecurityParameters,
     ) -> Result<HistoryEntry, Box<dyn Error>> {
         // handle indexes like MOEX
-        let last_column = match params.board.as_str() {
-            "SNDX" => "CURRENTVALUE",
-            "MMIX" => "CURRENTVALUE",
-            _ => "LAST",
+        let (open_column, last_column) = match params.board.as_str() {
+            "SNDX" => ("OPENVALUE", "CURRENTVALUE"),
+            "MMIX" => ("OPENVALUE", "CURRENTVALUE"),
+            _ => ("OPEN", "LAST"),
         };
 
         let url = format!(
-            "{}/iss/engines/{}/markets/{}/securities/{}.json?iss.meta=off&iss.only=marketdata&marketdata.columns=BOARDID,{},HIGH,LOW,VOLTODAY",
-            self.base_url, params.engine, params.market, ticker, last_column
+            "{}/iss/engines/{}/markets/{}/securities/{}.json?iss.meta=off&iss.only=marketdata&marketdata.columns=BOARDID,{},{},HIGH,LOW,VOLTODAY",
+            self.base_url, params.engine, params.market, ticker, open_column, last_column
         );
 
         debug!("get_security_current_price | url: {}", url);
@@ -187,10 +471,11 @@ impl MoexAPI {
                 continue;
             }
 
-            let close = entry[1].as_f64().unwrap_or_default();
-            let high = entry[2].as_f64().unwrap_or_default();
-            let low = entry[3].as_f64().unwrap_or_default();
-            let volume = entry[4].as_i64().unwrap_or_default();
+            let close = entry[2].as_f64().unwrap_or_default();
+            let open = entry[1].as_f64().unwrap_or(close);
+            let high = entry[3].as_f64().unwrap_or_default();
+            let low = entry[4].as_f64().unwrap_or_default();
+            let volume = entry[5].as_i64().unwrap_or_default();
             let facevalue = 1;
 
             if close == 0.0 || high == 0.0 || low == 0.0 || volume == 0 {
@@ -199,6 +484,7 @@ impl MoexAPI {
 
             return Ok(HistoryEntry {
                 date: chrono::Local::now().date_naive(),
+                open,
                 close,
                 high,
                 low,
@@ -209,6 +495,7 @@ impl MoexAPI {
         Err(Box::new(CustomError::NotFound))
     }
 
+    #[instrument(skip(self, params, redis_con))]
     async fn get_security_history_offset(
         &self,
         ticker: &str,
@@ -217,20 +504,32 @@ impl MoexAPI {
         redis_con: &mut redis::aio::MultiplexedConnection,
     ) -> Result<HistoryEntriesMoexMeta, Box<dyn Error>> {
         let url = format!(
-                    "{}/iss/history/engines/{}/markets/{}/boards/{}/securities/{}.json?iss.meta=off&start={}&history.columns=TRADEDATE,CLOSE,HIGH,LOW,VOLUME,FACEVALUE",
-                    self.base_url, params.engine, params.market, params.board, ticker, offset
-                );
+            "{}/iss/history/engines/{}/markets/{}/boards/{}/securities/{}.json?iss.meta=off&start={}&history.columns=TRADEDATE,OPEN,CLOSE,HIGH,LOW,VOLUME,FACEVALUE",
+            self.base_url, params.engine, params.market, params.board, ticker, offset
+        );
 
         debug!("get_security_history_offset | url: {}", url);
 
-        if redis_con.exists(&url).await? {
-            debug!("get_security_history_offset | cache hit | key: {}", url);
-            let cached: String = redis_con.get(&url).await?;
+        if redis_con
+            .exists(&url)
+            .instrument(redis_span("EXISTS"))
+            .await?
+        {
+            debug!(
+                cache_status = "hit",
+                "get_security_history_offset | cache hit | key: {}", url
+            );
+            record_cache("get_security_history_offset", true);
+            let cached: String = redis_con.get(&url).instrument(redis_span("GET")).await?;
             let cached_data: HistoryEntriesMoexMeta = serde_json::from_str(&cached)?;
             return Ok(cached_data);
         }
 
-        debug!("get_security_history_offset | cache miss | url: {}", url);
+        debug!(
+            cache_status = "miss",
+            "get_security_history_offset | cache miss | url: {}", url
+        );
+        record_cache("get_security_history_offset", false);
 
         let json = self
             .client
@@ -246,41 +545,48 @@ impl MoexAPI {
             page_size: json.history_cursor.data[0].2,
         };
 
+        // iss leaves out the requested columns a market does not have,
+        // e.g. FACEVALUE outside bonds and VOLUME for currencies
+        let columns = &json.history.columns;
+        let position = |name: &str| columns.iter().position(|column| column == name);
+        let (open, close, high, low, volume, facevalue) = (
+            position("OPEN"),
+            position("CLOSE"),
+            position("HIGH"),
+            position("LOW"),
+            position("VOLUME"),
+            position("FACEVALUE"),
+        );
+
         let mut history = Vec::new();
         for entry in json.history.data {
             let date =
                 NaiveDate::parse_from_str(entry[0].as_str().unwrap_or_default(), "%Y-%m-%d")?;
+            let value = |column: Option<usize>| column.and_then(|i| entry.get(i));
+            let price = |column| value(column).and_then(|v| v.as_f64()).unwrap_or_default();
 
-            // handle obligations
-            let facevalue = match entry.len() {
-                // if FACEVALUE exists then len is 6, so we need to handle it
-                6 => entry[5].as_i64().unwrap_or(1),
-                // otherwise set 1
-                _ => 1,
-            };
-
-            // handle currencies
-            let volume = match entry.len() {
-                1..5 => 0,
-                _ => entry[4].as_i64().unwrap_or_default(),
-            };
-
+            let close = price(close);
             history.push(HistoryEntry {
                 date,
-                close: entry[1].as_f64().unwrap_or_default(),
-                high: entry[2].as_f64().unwrap_or_default(),
-                low: entry[3].as_f64().unwrap_or_default(),
-                volume,
-                facevalue,
+                // no open on days without trades
+                open: value(open).and_then(|v| v.as_f64()).unwrap_or(close),
+                close,
+                high: price(high),
+                low: price(low),
+                volume: value(volume).and_then(|v| v.as_i64()).unwrap_or_default(),
+                facevalue: value(facevalue).and_then(|v| v.as_i64()).unwrap_or(1),
             })
         }
 
         let out = HistoryEntriesMoexMeta { history, meta };
 
-        if out.history.len() != 0 && out.history.len() as i64 % out.meta.page_size == 0 {
+        if !out.history.is_empty() && out.history.len() as i64 % out.meta.page_size == 0 {
             debug!("get_security_parameters | saving to cache");
             let serialized = serde_json::to_string(&out)?;
-            let _: () = redis_con.set(&url, &serialized).await?;
+            let _: () = redis_con
+                .set(&url, &serialized)
+                .instrument(redis_span("SET"))
+                .await?;
         }
 
         Ok(out)
@@ -311,6 +617,15 @@ struct HistoryCursor {
     page_size: i64,
 }
 
+struct PageCursor {
+    redis_con: redis::aio::MultiplexedConnection,
+    params: MoexSecurityParameters,
+    offset: i64,
+    total: i64,
+    facevalue: i64,
+    finished: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct HistoryEntriesMoexMeta {
     history: Vec<HistoryEntry>,
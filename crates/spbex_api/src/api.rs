@@ -1,28 +1,55 @@
 use history_model::HistoryEntry;
 use itertools::izip;
-use log::debug;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt;
+use tracing::{debug, instrument};
+use upstream_client::{UpstreamClient, UpstreamConfig, UpstreamStatus};
 
 pub struct SpbexAPI {
     base_url: String,
-    client: reqwest::Client,
+    client: UpstreamClient,
     headers: reqwest::header::HeaderMap,
 }
 
+impl Default for SpbexAPI {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SpbexAPI {
     pub fn new() -> Self {
+        Self::with_config(UpstreamConfig::default())
+    }
+
+    pub fn with_config(upstream: UpstreamConfig) -> Self {
         let mut reqwest_headers = reqwest::header::HeaderMap::new();
         reqwest_headers.insert(reqwest::header::USER_AGENT, "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36".parse().unwrap());
 
-        return SpbexAPI {
+        SpbexAPI {
             base_url: "https://investcab.ru/api".to_string(),
-            client: reqwest::Client::new(),
+            client: UpstreamClient::with_config("investcab", upstream),
             headers: reqwest_headers,
-        };
+        }
+    }
+
+    pub fn with_base_url(self, base_url: impl Into<String>) -> Self {
+        SpbexAPI {
+            base_url: base_url.into(),
+            ..self
+        }
+    }
+
+    pub fn upstream_status(&self) -> UpstreamStatus {
+        self.client.status()
+    }
+
+    pub fn reconfigure(&self, upstream: UpstreamConfig) {
+        self.client.reconfigure(upstream);
     }
 
+    #[instrument(skip(self), fields(exchange = "spbex"))]
     pub async fn get_ticker(&self, ticker: &str) -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
         let timerange = self.get_time_range();
         let url = format!(
@@ -45,22 +72,29 @@ impl SpbexAPI {
         let spbex_json: SpbexHistoryJSON =
             serde_json::from_str(&spbex_json_str[1..spbex_json_str.len() - 1])?;
 
-        if spbex_json.t.len() == 0 {
+        if spbex_json.t.is_empty() {
             return Err(Box::new(CustomError::NotFound));
         }
 
-        let history = izip!(&spbex_json.t, &spbex_json.h, &spbex_json.l, &spbex_json.c)
-            .map(|(t, h, l, c)| HistoryEntry {
-                date: chrono::DateTime::from_timestamp(*t, 0)
-                    .unwrap_or_default()
-                    .date_naive(),
-                close: *c,
-                high: *h,
-                low: *l,
-                volume: 0,
-                facevalue: 1,
-            })
-            .collect();
+        let history = izip!(
+            &spbex_json.t,
+            &spbex_json.o,
+            &spbex_json.h,
+            &spbex_json.l,
+            &spbex_json.c
+        )
+        .map(|(t, o, h, l, c)| HistoryEntry {
+            date: chrono::DateTime::from_timestamp(*t, 0)
+                .unwrap_or_default()
+                .date_naive(),
+            open: *o,
+            close: *c,
+            high: *h,
+            low: *l,
+            volume: 0,
+            facevalue: 1,
+        })
+        .collect();
 
         Ok(history)
     }
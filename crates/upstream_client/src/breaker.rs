@@ -0,0 +1,157 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// consecutive failed calls open the breaker, calls are rejected until the cooldown
+// ends. a single call is then let through (half-open), it closes the breaker on
+// success and opens it for another cooldown on failure. callers arriving while the
+// probe is in flight are rejected, a probe that never reports back expires after a cooldown
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    threshold: u32,
+    cooldown: Duration,
+    failures: u32,
+    open_until: Option<Instant>,
+    probe_since: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    // a zero threshold never opens
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            state: Mutex::new(State {
+                threshold,
+                cooldown,
+                ..Default::default()
+            }),
+        }
+    }
+
+    // new limits apply from the next failure, an open breaker keeps its cooldown
+    pub fn reconfigure(&self, threshold: u32, cooldown: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.threshold = threshold;
+        state.cooldown = cooldown;
+    }
+
+    // takes the half-open probe when it lets a call through after the cooldown
+    pub fn allows(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let Some(until) = state.open_until else {
+            return true;
+        };
+        if now < until {
+            return false;
+        }
+        if state
+            .probe_since
+            .is_some_and(|since| now < since + state.cooldown)
+        {
+            return false;
+        }
+        state.probe_since = Some(now);
+        true
+    }
+
+    // rejecting every call, a half-open breaker is not
+    pub fn is_open(&self, now: Instant) -> bool {
+        let state = self.state.lock().unwrap();
+        state.open_until.is_some_and(|until| now < until)
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.failures = 0;
+        state.open_until = None;
+        state.probe_since = None;
+    }
+
+    // true when this failure opened the breaker
+    pub fn record_failure(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.threshold == 0 {
+            return false;
+        }
+        state.failures += 1;
+        state.probe_since = None;
+        let half_open = state.open_until.is_some();
+        if half_open || state.failures >= state.threshold {
+            state.open_until = Some(now + state.cooldown);
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOLDOWN: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn breaker_pass_opens_after_threshold() {
+        let breaker = CircuitBreaker::new(2, COOLDOWN);
+        let now = Instant::now();
+        assert!(!breaker.record_failure(now));
+        assert!(breaker.allows(now));
+        assert!(breaker.record_failure(now));
+        assert!(breaker.is_open(now));
+        assert!(breaker.allows(now + COOLDOWN));
+    }
+
+    #[test]
+    fn breaker_pass_success_resets() {
+        let breaker = CircuitBreaker::new(2, COOLDOWN);
+        let now = Instant::now();
+        breaker.record_failure(now);
+        breaker.record_success();
+        assert!(!breaker.record_failure(now));
+        assert!(breaker.allows(now));
+    }
+
+    #[test]
+    fn breaker_pass_half_open_failure_reopens() {
+        let breaker = CircuitBreaker::new(3, COOLDOWN);
+        let now = Instant::now();
+        for _ in 0..3 {
+            breaker.record_failure(now);
+        }
+        let later = now + COOLDOWN;
+        assert!(breaker.allows(later));
+        assert!(!breaker.allows(later));
+        assert!(breaker.record_failure(later));
+        assert!(breaker.is_open(later));
+        assert!(breaker.allows(later + COOLDOWN));
+    }
+
+    #[test]
+    fn breaker_pass_single_probe() {
+        let breaker = CircuitBreaker::new(1, COOLDOWN);
+        let now = Instant::now();
+        breaker.record_failure(now);
+        let later = now + COOLDOWN;
+        assert!(breaker.allows(later));
+        assert!(!breaker.allows(later));
+        assert!(!breaker.is_open(later));
+        // an abandoned probe is replaced after another cooldown
+        assert!(breaker.allows(later + COOLDOWN));
+        breaker.record_success();
+        assert!(breaker.allows(later));
+        assert!(breaker.allows(later));
+    }
+
+    #[test]
+    fn breaker_pass_disabled() {
+        let breaker = CircuitBreaker::new(0, COOLDOWN);
+        let now = Instant::now();
+        for _ in 0..10 {
+            assert!(!breaker.record_failure(now));
+        }
+        assert!(breaker.allows(now));
+    }
+}
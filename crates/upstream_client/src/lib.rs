@@ -0,0 +1,540 @@
+use reqwest::header::HeaderMap;
+use reqwest::{RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{Instrument, debug, field, info_span, warn};
+
+use breaker::CircuitBreaker;
+use limiter::RateLimiter;
+pub use limiter::{MAX_REQUESTS_PER_SECOND, MIN_REQUESTS_PER_SECOND};
+
+mod breaker;
+mod limiter;
+
+pub const MAX_RETRIES: u32 = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpstreamConfig {
+    pub connect_timeout: Duration,
+    // deadline of a whole call: queueing, every attempt, the backoff and the body
+    pub timeout: Duration,
+    // between reads of the response, a slow but progressing body is fine
+    pub read_timeout: Duration,
+    // extra attempts after a transport error, a 429 or a 5xx, at most MAX_RETRIES
+    pub retries: u32,
+    // delay before the first retry, doubled for every next one
+    pub backoff: Duration,
+    // consecutive failed requests that open the breaker, 0 disables it
+    pub breaker_threshold: u32,
+    pub breaker_cooldown: Duration,
+    // requests sent at once, 0 is unlimited
+    pub max_in_flight: usize,
+    // 0 is unlimited
+    pub requests_per_second: f64,
+}
+
+impl Default for UpstreamConfig {
+    fn default() -> Self {
+        UpstreamConfig {
+            connect_timeout: Duration::from_secs(5),
+            timeout: Duration::from_secs(60),
+            read_timeout: Duration::from_secs(30),
+            retries: 2,
+            backoff: Duration::from_millis(200),
+            breaker_threshold: 5,
+            breaker_cooldown: Duration::from_secs(30),
+            max_in_flight: 0,
+            requests_per_second: 0.0,
+        }
+    }
+}
+
+impl UpstreamConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.retries > MAX_RETRIES {
+            return Err(format!(
+                "retries must be at most {}, got {}",
+                MAX_RETRIES, self.retries
+            ));
+        }
+        if self.timeout.is_zero() {
+            return Err("timeout must be positive".to_string());
+        }
+        let rps = self.requests_per_second;
+        if rps != 0.0 && !(MIN_REQUESTS_PER_SECOND..=MAX_REQUESTS_PER_SECOND).contains(&rps) {
+            return Err(format!(
+                "requests per second must be 0 or between {} and {}, got {}",
+                MIN_REQUESTS_PER_SECOND, MAX_REQUESTS_PER_SECOND, rps
+            ));
+        }
+        Ok(())
+    }
+}
+
+// outcome of the latest requests, for readiness checks
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct UpstreamStatus {
+    pub last_success: Option<Instant>,
+    pub last_failure: Option<Instant>,
+    // of the latest request, retries included
+    pub last_duration: Option<Duration>,
+    pub circuit_open: bool,
+}
+
+impl UpstreamStatus {
+    // no request finished within the window, the provider may be down unnoticed
+    pub fn is_idle(&self, now: Instant, window: Duration) -> bool {
+        let recent =
+            |at: Option<Instant>| at.is_some_and(|at| now.saturating_duration_since(at) <= window);
+        !recent(self.last_success) && !recent(self.last_failure)
+    }
+
+    // down while the breaker is open, or when the latest request failed and
+    // nothing succeeded within the window. no requests yet counts as up
+    pub fn is_up(&self, now: Instant, window: Duration) -> bool {
+        if self.circuit_open {
+            return false;
+        }
+        let Some(failure) = self.last_failure else {
+            return true;
+        };
+        self.last_success.is_some_and(|success| {
+            success > failure || now.saturating_duration_since(success) <= window
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum UpstreamError {
+    Request(reqwest::Error),
+    // failing fast while the upstream is considered down
+    CircuitOpen(&'static str),
+    // the call ran past UpstreamConfig::timeout
+    Timeout(&'static str),
+}
+
+impl fmt::Display for UpstreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpstreamError::Request(e) => write!(f, "{}", e),
+            UpstreamError::CircuitOpen(provider) => {
+                write!(f, "{} is unavailable, circuit breaker is open", provider)
+            }
+            UpstreamError::Timeout(provider) => write!(f, "{} did not answer in time", provider),
+        }
+    }
+}
+
+impl Error for UpstreamError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            UpstreamError::Request(e) => Some(e),
+            UpstreamError::CircuitOpen(_) | UpstreamError::Timeout(_) => None,
+        }
+    }
+}
+
+// http client shared by the providers, every call is recorded per provider.
+// clones share the circuit breaker and the limits
+#[derive(Debug, Clone)]
+pub struct UpstreamClient {
+    provider: &'static str,
+    breaker: Arc<CircuitBreaker>,
+    limits: Arc<RwLock<Arc<Limits>>>,
+    status: Arc<Mutex<UpstreamStatus>>,
+}
+
+// replaced as a whole on reconfigure, a call keeps the limits it started with
+#[derive(Debug)]
+struct Limits {
+    client: reqwest::Client,
+    config: UpstreamConfig,
+    in_flight: Option<Arc<Semaphore>>,
+    rate: Option<RateLimiter>,
+}
+
+impl Limits {
+    fn new(config: UpstreamConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .connect_timeout(config.connect_timeout)
+            .timeout(config.timeout)
+            .read_timeout(config.read_timeout)
+            .build()
+            .expect("Failed to create HTTP client");
+        let in_flight = (config.max_in_flight > 0).then(|| Semaphore::new(config.max_in_flight));
+        let rate = (config.requests_per_second > 0.0)
+            .then(|| RateLimiter::new(config.requests_per_second));
+
+        Limits {
+            client,
+            config,
+            in_flight: in_flight.map(Arc::new),
+            rate,
+        }
+    }
+}
+
+// the in-flight slot is held until the body is read
+#[derive(Debug)]
+pub struct UpstreamResponse {
+    response: Response,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl UpstreamResponse {
+    pub fn status(&self) -> reqwest::StatusCode {
+        self.response.status()
+    }
+
+    pub async fn json<T: DeserializeOwned>(self) -> reqwest::Result<T> {
+        self.response.json().await
+    }
+
+    pub async fn text(self) -> reqwest::Result<String> {
+        self.response.text().await
+    }
+}
+
+pub struct UpstreamRequest<'a> {
+    upstream: &'a UpstreamClient,
+    limits: Arc<Limits>,
+    url: String,
+    request: RequestBuilder,
+}
+
+impl UpstreamClient {
+    pub fn new(provider: &'static str) -> Self {
+        Self::with_config(provider, UpstreamConfig::default())
+    }
+
+    pub fn with_config(provider: &'static str, config: UpstreamConfig) -> Self {
+        let breaker = CircuitBreaker::new(config.breaker_threshold, config.breaker_cooldown);
+
+        UpstreamClient {
+            provider,
+            breaker: Arc::new(breaker),
+            limits: Arc::new(RwLock::new(Arc::new(Limits::new(config)))),
+            status: Arc::default(),
+        }
+    }
+
+    // applies to calls started afterwards, calls in flight finish under the old
+    // limits. the breaker keeps its failure count, the status is kept as well
+    pub fn reconfigure(&self, config: UpstreamConfig) {
+        self.breaker
+            .reconfigure(config.breaker_threshold, config.breaker_cooldown);
+        let mut limits = self.limits.write().unwrap();
+        if limits.config != config {
+            *limits = Arc::new(Limits::new(config));
+        }
+    }
+
+    pub fn config(&self) -> UpstreamConfig {
+        self.limits.read().unwrap().config.clone()
+    }
+
+    pub fn provider(&self) -> &'static str {
+        self.provider
+    }
+
+    pub fn status(&self) -> UpstreamStatus {
+        UpstreamStatus {
+            circuit_open: self.breaker.is_open(Instant::now()),
+            ..*self.status.lock().unwrap()
+        }
+    }
+
+    pub fn get<U: AsRef<str>>(&self, url: U) -> UpstreamRequest<'_> {
+        let limits = self.limits.read().unwrap().clone();
+        UpstreamRequest {
+            upstream: self,
+            request: limits.client.get(url.as_ref()),
+            limits,
+            url: url.as_ref().to_string(),
+        }
+    }
+
+    async fn attempt(
+        &self,
+        limits: &Limits,
+        url: &str,
+        request: RequestBuilder,
+    ) -> reqwest::Result<UpstreamResponse> {
+        let provider = self.provider;
+        let span = info_span!(
+            "upstream request",
+            otel.kind = "client",
+            provider,
+            http.request.method = "GET",
+            url.full = %url,
+            http.response.status_code = field::Empty,
+        );
+
+        // excess requests queue here instead of hitting the upstream
+        let queued = Instant::now();
+        let permit = match &limits.in_flight {
+            Some(in_flight) => Some(
+                in_flight
+                    .clone()
+                    .acquire_owned()
+                    .instrument(span.clone())
+                    .await
+                    .expect("semaphore is never closed"),
+            ),
+            None => None,
+        };
+        if let Some(rate) = &limits.rate {
+            rate.acquire().instrument(span.clone()).await;
+        }
+        metrics::histogram!("upstream_queue_duration_seconds", "provider" => provider)
+            .record(queued.elapsed().as_secs_f64());
+
+        let start = Instant::now();
+        let result = request.send().instrument(span.clone()).await;
+        let elapsed = start.elapsed();
+
+        let status = match &result {
+            Ok(response) => {
+                span.record("http.response.status_code", response.status().as_u16());
+                response.status().as_u16().to_string()
+            }
+            Err(_) => "error".to_string(),
+        };
+        debug!(
+            provider,
+            upstream_url = %url,
+            status = %status,
+            duration_ms = elapsed.as_millis() as u64,
+            "upstream request"
+        );
+
+        metrics::counter!("upstream_requests_total", "provider" => provider, "status" => status)
+            .increment(1);
+        metrics::histogram!("upstream_request_duration_seconds", "provider" => provider)
+            .record(elapsed.as_secs_f64());
+        if !result.as_ref().is_ok_and(|r| r.status().is_success()) {
+            metrics::counter!("upstream_errors_total", "provider" => provider).increment(1);
+        }
+
+        result.map(|response| UpstreamResponse {
+            response,
+            _permit: permit,
+        })
+    }
+}
+
+impl UpstreamRequest<'_> {
+    pub fn headers(self, headers: HeaderMap) -> Self {
+        UpstreamRequest {
+            request: self.request.headers(headers),
+            ..self
+        }
+    }
+
+    pub async fn send(self) -> Result<UpstreamResponse, UpstreamError> {
+        let UpstreamRequest {
+            upstream,
+            limits,
+            url,
+            mut request,
+        } = self;
+        let provider = upstream.provider;
+        if !upstream.breaker.allows(Instant::now()) {
+            metrics::counter!("upstream_rejected_total", "provider" => provider).increment(1);
+            return Err(UpstreamError::CircuitOpen(provider));
+        }
+
+        let start = Instant::now();
+        let mut attempt = 0;
+        let attempts = async {
+            loop {
+                // send consumes the builder, so the next attempt needs a copy made up front
+                let retry = if attempt < limits.config.retries {
+                    request.try_clone()
+                } else {
+                    None
+                };
+                let result = upstream.attempt(&limits, &url, request).await;
+                match retry {
+                    Some(next) if is_failure(&result) => {
+                        // frees the in-flight slot during the backoff
+                        drop(result);
+                        let delay = backoff(limits.config.backoff, attempt);
+                        warn!(
+                            provider,
+                            upstream_url = %url,
+                            attempt = attempt + 1,
+                            delay_ms = delay.as_millis() as u64,
+                            "upstream request failed, retrying"
+                        );
+                        metrics::counter!("upstream_retries_total", "provider" => provider)
+                            .increment(1);
+                        tokio::time::sleep(delay).await;
+                        request = next;
+                        attempt += 1;
+                    }
+                    _ => break result,
+                }
+            }
+        };
+        // reqwest times out a single attempt, this bounds the retries and the backoff as well
+        let result = match tokio::time::timeout(limits.config.timeout, attempts).await {
+            Ok(result) => result.map_err(UpstreamError::Request),
+            Err(_) => {
+                warn!(provider, upstream_url = %url, "upstream call timed out");
+                Err(UpstreamError::Timeout(provider))
+            }
+        };
+
+        let now = Instant::now();
+        let failed = match &result {
+            Ok(response) => is_failure_status(response.status()),
+            Err(_) => true,
+        };
+        {
+            let mut status = upstream.status.lock().unwrap();
+            status.last_duration = Some(now - start);
+            match failed {
+                true => status.last_failure = Some(now),
+                false => status.last_success = Some(now),
+            }
+        }
+        if !failed {
+            upstream.breaker.record_success();
+            metrics::gauge!("upstream_circuit_open", "provider" => provider).set(0.0);
+        } else if upstream.breaker.record_failure(now) {
+            warn!(provider, "circuit breaker opened");
+            metrics::gauge!("upstream_circuit_open", "provider" => provider).set(1.0);
+        }
+
+        result
+    }
+}
+
+// a 4xx is an answer, the upstream itself is fine. a 429 is throttling and is backed off
+fn is_failure_status(status: reqwest::StatusCode) -> bool {
+    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+fn is_failure(result: &reqwest::Result<UpstreamResponse>) -> bool {
+    match result {
+        Ok(response) => is_failure_status(response.status()),
+        Err(_) => true,
+    }
+}
+
+fn backoff(base: Duration, attempt: u32) -> Duration {
+    base.saturating_mul(1 << attempt.min(16))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_pass() {
+        let base = Duration::from_millis(200);
+        assert_eq!(backoff(base, 0), Duration::from_millis(200));
+        assert_eq!(backoff(base, 1), Duration::from_millis(400));
+        assert_eq!(backoff(base, 3), Duration::from_millis(1600));
+        assert_eq!(backoff(Duration::MAX, 2), Duration::MAX);
+    }
+
+    #[test]
+    fn validate_fail_retries_and_timeout() {
+        let retries = UpstreamConfig {
+            retries: u32::MAX,
+            ..Default::default()
+        };
+        assert!(retries.validate().is_err());
+        let timeout = UpstreamConfig {
+            timeout: Duration::ZERO,
+            ..Default::default()
+        };
+        assert!(timeout.validate().is_err());
+        assert!(UpstreamConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn is_failure_status_pass() {
+        assert!(is_failure_status(reqwest::StatusCode::BAD_GATEWAY));
+        assert!(is_failure_status(reqwest::StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_failure_status(reqwest::StatusCode::NOT_FOUND));
+        assert!(!is_failure_status(reqwest::StatusCode::OK));
+    }
+
+    #[test]
+    fn reconfigure_pass() {
+        let client = UpstreamClient::new("test");
+        let shared = client.clone();
+        let config = UpstreamConfig {
+            retries: 5,
+            max_in_flight: 4,
+            ..Default::default()
+        };
+        client.reconfigure(config.clone());
+        assert_eq!(shared.config(), config);
+        let request = shared.get("http://localhost/");
+        client.reconfigure(UpstreamConfig::default());
+        assert_eq!(request.limits.config, config);
+        assert_eq!(shared.config(), UpstreamConfig::default());
+    }
+
+    #[test]
+    fn validate_fail_requests_per_second() {
+        let config = |requests_per_second| UpstreamConfig {
+            requests_per_second,
+            ..Default::default()
+        };
+        assert!(config(0.0).validate().is_ok());
+        assert!(config(2.5).validate().is_ok());
+        assert!(config(1e-300).validate().is_err());
+        assert!(config(-1.0).validate().is_err());
+        assert!(config(f64::NAN).validate().is_err());
+        assert!(config(f64::INFINITY).validate().is_err());
+    }
+
+    #[test]
+    fn status_pass_is_up() {
+        let window = Duration::from_secs(60);
+        let now = Instant::now();
+        let earlier = now - Duration::from_secs(30);
+        let long_ago = now - Duration::from_secs(300);
+
+        assert!(UpstreamStatus::default().is_up(now, window));
+        let failing = UpstreamStatus {
+            last_success: Some(long_ago),
+            last_failure: Some(now),
+            ..Default::default()
+        };
+        assert!(!failing.is_up(now, window));
+        let flaky = UpstreamStatus {
+            last_success: Some(earlier),
+            ..failing
+        };
+        assert!(flaky.is_up(now, window));
+        let recovered = UpstreamStatus {
+            last_success: Some(now),
+            last_failure: Some(long_ago),
+            ..Default::default()
+        };
+        assert!(recovered.is_up(now, window));
+        let open = UpstreamStatus {
+            circuit_open: true,
+            ..recovered
+        };
+        assert!(!open.is_up(now, window));
+
+        assert!(UpstreamStatus::default().is_idle(now, window));
+        assert!(!failing.is_idle(now, window));
+        let quiet = UpstreamStatus {
+            last_success: Some(long_ago),
+            ..Default::default()
+        };
+        assert!(quiet.is_idle(now, window));
+    }
+}
@@ -0,0 +1,72 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// configured rates outside this range are rejected, see UpstreamConfig::validate
+pub const MIN_REQUESTS_PER_SECOND: f64 = 0.001;
+pub const MAX_REQUESTS_PER_SECOND: f64 = 10_000.0;
+
+// spaces requests evenly, callers over the budget wait for their slot in arrival order
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64) -> Self {
+        // clamped so the interval always fits a Duration
+        let rate = requests_per_second.clamp(MIN_REQUESTS_PER_SECOND, MAX_REQUESTS_PER_SECOND);
+        RateLimiter {
+            interval: Duration::from_secs_f64(1.0 / rate),
+            next: Mutex::new(None),
+        }
+    }
+
+    // time the caller may send at, the slot is taken even if the caller gives up
+    pub fn reserve(&self, now: Instant) -> Instant {
+        let mut next = self.next.lock().unwrap();
+        let slot = next.map_or(now, |next| next.max(now));
+        *next = Some(slot + self.interval);
+        slot
+    }
+
+    pub async fn acquire(&self) {
+        let slot = self.reserve(Instant::now());
+        tokio::time::sleep_until(slot.into()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserve_pass_spaces_requests() {
+        let limiter = RateLimiter::new(4.0);
+        let now = Instant::now();
+        assert_eq!(limiter.reserve(now), now);
+        assert_eq!(limiter.reserve(now), now + Duration::from_millis(250));
+        assert_eq!(limiter.reserve(now), now + Duration::from_millis(500));
+    }
+
+    #[test]
+    fn new_pass_extreme_rates() {
+        let now = Instant::now();
+        let slow = RateLimiter::new(1e-300);
+        slow.reserve(now);
+        assert_eq!(slow.reserve(now), now + Duration::from_secs(1000));
+        let fast = RateLimiter::new(f64::INFINITY);
+        fast.reserve(now);
+        assert_eq!(fast.reserve(now), now + Duration::from_micros(100));
+    }
+
+    #[test]
+    fn reserve_pass_idle_does_not_accumulate() {
+        let limiter = RateLimiter::new(4.0);
+        let now = Instant::now();
+        limiter.reserve(now);
+        let later = now + Duration::from_secs(10);
+        assert_eq!(limiter.reserve(later), later);
+        assert_eq!(limiter.reserve(later), later + Duration::from_millis(250));
+    }
+}